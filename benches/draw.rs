@@ -0,0 +1,80 @@
+//! Times building and drawing a frame of 10k primitives, with and without instancing.
+//!
+//! Needs a display to open a (hidden) window on, run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use glium::{backend::glutin::SimpleWindowBuilder, winit, Surface};
+use overlaylib::{
+    frame::Frame,
+    primitives::{Circle, Line, Rectangle, Text},
+    Overlay,
+};
+
+const PRIMITIVES: usize = 10_000;
+const ITERATIONS: u32 = 100;
+
+fn main() {
+    let event_loop = match winit::event_loop::EventLoop::builder().build() {
+        Ok(event_loop) => event_loop,
+        Err(e) => {
+            eprintln!("skipping benchmark, no display available: {}", e);
+            return;
+        }
+    };
+    let attributes = winit::window::Window::default_attributes().with_visible(false);
+    let (_window, display) = SimpleWindowBuilder::new()
+        .set_window_builder(attributes)
+        .with_inner_size(1920, 1080)
+        .build(&event_loop);
+
+    let overlay = Overlay::initialize(&display).expect("failed to initialize the overlay");
+
+    for instanced in [false, true] {
+        let mut build = Duration::ZERO;
+        let mut draw = Duration::ZERO;
+
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            let mut frame = overlay.new_frame();
+            add_primitives(&mut frame, instanced);
+            build += start.elapsed();
+
+            let start = Instant::now();
+            let mut target = display.draw();
+            target.clear_color(0.0, 0.0, 0.0, 0.0);
+            overlay
+                .draw(&mut target, &mut frame)
+                .expect("failed to draw the overlay");
+            target.finish().expect("failed to swap buffers");
+            draw += start.elapsed();
+        }
+
+        println!(
+            "{} primitives{}: {:?} building the frame, {:?} drawing it (mean of {} iterations)",
+            PRIMITIVES,
+            if instanced { ", instanced" } else { "" },
+            build / ITERATIONS,
+            draw / ITERATIONS,
+            ITERATIONS
+        );
+    }
+}
+
+/// Adds rectangles, circles, lines and text in equal parts.
+fn add_primitives(frame: &mut Frame, instanced: bool) {
+    for i in 0..PRIMITIVES {
+        let x = (i % 100) as f32 * 19.0;
+        let y = (i / 100) as f32 * 10.0;
+        let rectangle = Rectangle::new().position([x, y]).dimensions([16.0, 8.0]);
+        let circle = Circle::new().position([x, y]).radius(4.0_f32).filled(true);
+        match i % 4 {
+            0 if instanced => frame.add_instanced(rectangle),
+            0 => frame.add(rectangle),
+            1 if instanced => frame.add_instanced(circle),
+            1 => frame.add(circle),
+            2 => frame.add(Line::new().start([x, y]).end([x + 16.0, y + 8.0])),
+            _ => frame.add(Text::new("esp").position([x, y])),
+        }
+    }
+}
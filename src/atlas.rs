@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+
+use glium::{
+    backend::Facade, framebuffer::SimpleFrameBuffer, implement_vertex, texture::Texture2dArray,
+    Texture2d,
+    uniforms::MagnifySamplerFilter, BlitTarget, CapabilitiesSource, Surface,
+};
+
+use crate::{frame::TexturedBuffer, renderer::ResourceHandle, texture::Texture2D, Vertex};
+
+/// Size of an atlas page, if the GPU supports textures that large.
+const PAGE_SIZE: u32 = 2048;
+/// Pages are only added up to this count, then textures are drawn on their own again.
+const MAX_PAGES: u32 = 8;
+/// Side of the white block reserved in the corner of the first page, sampled by untextured shapes.
+const WHITE_SIZE: u32 = 2;
+
+/// The vertex layout the overlay uploads, with the atlas page to sample from.
+#[derive(Copy, Clone, Default)]
+pub(crate) struct AtlasVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+    pub layer: f32,
+    /// 1.0 if the texture sampled has premultiplied alpha, 0.0 otherwise.
+    pub tex_premultiplied: f32,
+}
+
+implement_vertex!(
+    AtlasVertex,
+    position,
+    tex_coords,
+    color,
+    layer,
+    tex_premultiplied
+);
+
+/// Where a texture ended up in the atlas.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AtlasRegion {
+    layer: f32,
+    offset: [f32; 2],
+    scale: [f32; 2],
+    premultiplied: bool,
+}
+
+impl AtlasRegion {
+    /// Converts a vertex of the packed texture to one sampling the atlas.
+    pub fn map(&self, vertex: &Vertex) -> AtlasVertex {
+        AtlasVertex {
+            position: vertex.position,
+            tex_coords: [
+                self.offset[0] + vertex.tex_coords[0] * self.scale[0],
+                self.offset[1] + vertex.tex_coords[1] * self.scale[1],
+            ],
+            color: vertex.color,
+            layer: self.layer,
+            tex_premultiplied: if self.premultiplied { 1.0 } else { 0.0 },
+        }
+    }
+}
+
+/// A row of equally tall slots, filled from left to right.
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+    /// Spans freed by dropped textures, as (x, width), reused before the rest of the row.
+    free: Vec<(u32, u32)>,
+}
+
+impl Shelf {
+    fn can_fit(&self, width: u32, page_size: u32) -> bool {
+        self.free.iter().any(|&(_, free)| free >= width) || self.x + width <= page_size
+    }
+
+    /// Reserves `width` texels of the row, returning where they start.
+    fn take(&mut self, width: u32, page_size: u32) -> Option<u32> {
+        if let Some(index) = self.free.iter().position(|&(_, free)| free >= width) {
+            let (x, free) = self.free[index];
+            if free == width {
+                self.free.swap_remove(index);
+            } else {
+                self.free[index] = (x + width, free - width);
+            }
+            return Some(x);
+        }
+        if self.x + width > page_size {
+            return None;
+        }
+        let x = self.x;
+        self.x += width;
+        Some(x)
+    }
+
+    /// Gives back a span reserved with `take`.
+    fn release(&mut self, x: u32, width: u32) {
+        // merged with its free neighbours, so that wider textures fit in the freed space
+        let mut span = (x, width);
+        self.free.retain(|&(free_x, free_width)| {
+            if free_x + free_width == span.0 {
+                span = (free_x, free_width + span.1);
+                false
+            } else if span.0 + span.1 == free_x {
+                span.1 += free_width;
+                false
+            } else {
+                true
+            }
+        });
+        if span.0 + span.1 == self.x {
+            self.x = span.0;
+        } else {
+            self.free.push(span);
+        }
+    }
+}
+
+/// The area a texture was packed into.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    layer: usize,
+    shelf: usize,
+    x: u32,
+    width: u32,
+}
+
+/// A texture seen by the atlas, kept until the texture is dropped.
+struct Packed {
+    handle: ResourceHandle,
+    /// None if the texture didn't fit.
+    region: Option<AtlasRegion>,
+    slot: Option<Slot>,
+}
+
+/// Shelf packer for a single page.
+#[derive(Default)]
+struct Page {
+    shelves: Vec<Shelf>,
+    next_y: u32,
+}
+
+impl Page {
+    /// Reserves a `width` x `height` area, returning its bottom left corner and its shelf.
+    fn allocate(&mut self, width: u32, height: u32, page_size: u32) -> Option<(u32, u32, usize)> {
+        // the shortest shelf that fits wastes the least space
+        let shelf = self
+            .shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| shelf.height >= height && shelf.can_fit(width, page_size))
+            .min_by_key(|(_, shelf)| shelf.height)
+            .map(|(index, _)| index);
+        if let Some(index) = shelf {
+            let shelf = &mut self.shelves[index];
+            let x = shelf.take(width, page_size)?;
+            return Some((x, shelf.y, index));
+        }
+
+        if width > page_size || self.next_y + height > page_size {
+            return None;
+        }
+        let position = (0, self.next_y, self.shelves.len());
+        self.shelves.push(Shelf {
+            y: self.next_y,
+            height,
+            x: width,
+            free: Vec::new(),
+        });
+        self.next_y += height;
+        Some(position)
+    }
+}
+
+/// Small textures copied into the pages of a texture array, so that geometry using any of them
+/// can be drawn in a single draw call.
+///
+/// Textures are copied on the GPU the first time they are drawn. Once they are dropped, `purge`
+/// frees their space for other textures.
+#[derive(Default)]
+pub(crate) struct TextureAtlas {
+    array: Option<Texture2dArray>,
+    page_size: u32,
+    pages: Vec<Page>,
+    /// Every texture seen so far, keyed by `Texture2D::id`.
+    regions: HashMap<u64, Packed>,
+}
+
+impl TextureAtlas {
+    pub fn texture(&self) -> Option<&Texture2dArray> {
+        self.array.as_ref()
+    }
+
+    /// Forgets every packed texture and frees the pages.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Frees the space of textures that were dropped.
+    pub fn purge(&mut self) {
+        let pages = &mut self.pages;
+        self.regions.retain(|_, packed| {
+            if packed.handle.is_alive() {
+                return true;
+            }
+            if let Some(slot) = packed.slot {
+                pages[slot.layer].shelves[slot.shelf].release(slot.x, slot.width);
+            }
+            false
+        });
+    }
+
+    /// Returns the region `buffer` samples from, packing its texture if needed.
+    ///
+    /// Untextured buffers get the white block. Returns None for buffers that have to be drawn
+    /// with their own texture: subpixel text, materials, render targets, textures too big for a
+    /// page and texture coordinates that rely on wrapping.
+    ///
+    /// `source` is the GPU copy of the buffer's texture, which gets copied into the atlas.
+    pub fn resolve<F>(
+        &mut self,
+        facade: &F,
+        buffer: &TexturedBuffer<'_>,
+        source: Option<&Texture2d>,
+    ) -> Option<AtlasRegion>
+    where
+        F: ?Sized + Facade,
+    {
+        if buffer.subpixel || buffer.material.is_some() {
+            return None;
+        }
+        let Some(texture) = buffer.texture else {
+            return self.white(facade);
+        };
+        // render targets change whenever they are drawn into, a copy would go stale
+        if texture.is_render_target() {
+            return None;
+        }
+        let wraps = buffer.vertices.iter().any(|vertex| {
+            vertex
+                .tex_coords
+                .iter()
+                .any(|coordinate| !(0.0..=1.0).contains(coordinate))
+        });
+        if wraps {
+            return None;
+        }
+
+        if let Some(packed) = self.regions.get(&texture.id()) {
+            return packed.region;
+        }
+        let packed = self.insert(facade, texture, source?);
+        let region = packed.region;
+        self.regions.insert(texture.id(), packed);
+        region
+    }
+
+    /// Returns a region whose every texture coordinate samples opaque white.
+    fn white<F>(&mut self, facade: &F) -> Option<AtlasRegion>
+    where
+        F: ?Sized + Facade,
+    {
+        if self.array.is_none() {
+            self.grow(facade)?;
+        }
+        // the center of the white block, so that linear filtering never reaches its neighbours
+        let center = (WHITE_SIZE / 2) as f32 / self.page_size as f32;
+        Some(AtlasRegion {
+            layer: 0.0,
+            offset: [center, center],
+            scale: [0.0, 0.0],
+            premultiplied: false,
+        })
+    }
+
+    /// Copies a texture into the atlas. The returned entry has no region if it didn't fit.
+    fn insert<F>(&mut self, facade: &F, texture: &Texture2D, source: &Texture2d) -> Packed
+    where
+        F: ?Sized + Facade,
+    {
+        let packed = self.pack(facade, texture, source);
+        Packed {
+            handle: texture.handle(),
+            region: packed.map(|(region, _)| region),
+            slot: packed.map(|(_, slot)| slot),
+        }
+    }
+
+    /// Reserves an area for a texture and copies it there.
+    fn pack<F>(
+        &mut self,
+        facade: &F,
+        texture: &Texture2D,
+        source: &Texture2d,
+    ) -> Option<(AtlasRegion, Slot)>
+    where
+        F: ?Sized + Facade,
+    {
+        if self.array.is_none() {
+            self.grow(facade)?;
+        }
+        let (width, height) = texture.dimensions();
+        if width == 0 || height == 0 {
+            return None;
+        }
+        // one texel of padding on each side, so linear filtering doesn't bleed between textures
+        let (padded_width, padded_height) = (width + 2, height + 2);
+        if padded_width > self.page_size || padded_height > self.page_size {
+            return None;
+        }
+
+        let page_size = self.page_size;
+        let allocation = self.pages.iter_mut().enumerate().find_map(|(layer, page)| {
+            let (x, y, shelf) = page.allocate(padded_width, padded_height, page_size)?;
+            Some((layer as u32, x, y, shelf))
+        });
+        let (layer, x, y, shelf) = match allocation {
+            Some(allocation) => allocation,
+            None => {
+                let layer = self.grow(facade)?;
+                let (x, y, shelf) =
+                    self.pages[layer as usize].allocate(padded_width, padded_height, page_size)?;
+                (layer, x, y, shelf)
+            }
+        };
+        let slot = Slot {
+            layer: layer as usize,
+            shelf,
+            x,
+            width: padded_width,
+        };
+
+        let target = self
+            .array
+            .as_ref()
+            .and_then(|array| array.main_level().layer(layer))
+            .and_then(|layer| SimpleFrameBuffer::new(facade, layer).ok());
+        let Some(target) = target else {
+            self.pages[slot.layer].shelves[shelf].release(x, padded_width);
+            return None;
+        };
+        copy_extruded(
+            &source.as_surface(),
+            &target,
+            (width, height),
+            (x + 1, y + 1),
+        );
+
+        let page_size = page_size as f32;
+        let region = AtlasRegion {
+            layer: layer as f32,
+            offset: [(x + 1) as f32 / page_size, (y + 1) as f32 / page_size],
+            scale: [width as f32 / page_size, height as f32 / page_size],
+            premultiplied: texture.premultiplied,
+        };
+        Some((region, slot))
+    }
+
+    /// Adds a page, copying the existing ones into a larger texture array.
+    ///
+    /// Returns the index of the new page.
+    fn grow<F>(&mut self, facade: &F) -> Option<u32>
+    where
+        F: ?Sized + Facade,
+    {
+        let layers = self.pages.len() as u32 + 1;
+        if layers > MAX_PAGES {
+            return None;
+        }
+        if self.array.is_none() {
+            let max_size = facade.get_context().get_capabilities().max_texture_size as u32;
+            self.page_size = PAGE_SIZE.min(max_size);
+        }
+        let size = self.page_size;
+        let array = Texture2dArray::empty(facade, size, size, layers).ok()?;
+
+        for layer in 0..layers {
+            let mut target =
+                SimpleFrameBuffer::new(facade, array.main_level().layer(layer)?).ok()?;
+            match self
+                .array
+                .as_ref()
+                .and_then(|old| old.main_level().layer(layer))
+            {
+                Some(old) => {
+                    let source = SimpleFrameBuffer::new(facade, old).ok()?;
+                    source.blit_whole_color_to(
+                        &target,
+                        &BlitTarget {
+                            left: 0,
+                            bottom: 0,
+                            width: size as i32,
+                            height: size as i32,
+                        },
+                        MagnifySamplerFilter::Nearest,
+                    );
+                }
+                // fresh texture contents are undefined
+                None => target.clear_color(0.0, 0.0, 0.0, 0.0),
+            }
+        }
+
+        let mut page = Page::default();
+        if self.pages.is_empty() {
+            let white = glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: WHITE_SIZE,
+                height: WHITE_SIZE,
+            };
+            let mut target = SimpleFrameBuffer::new(facade, array.main_level().layer(0)?).ok()?;
+            target.clear(Some(&white), Some((1.0, 1.0, 1.0, 1.0)), false, None, None);
+            page.allocate(WHITE_SIZE, WHITE_SIZE, size);
+        }
+        self.pages.push(page);
+        self.array = Some(array);
+        Some(layers - 1)
+    }
+}
+
+/// Copies a `width` x `height` surface to `position`, repeating its outermost texels once around it.
+fn copy_extruded<S: Surface, T: Surface>(
+    source: &S,
+    target: &T,
+    (width, height): (u32, u32),
+    (x, y): (u32, u32),
+) {
+    let (right, top) = (width - 1, height - 1);
+    // (source left, source bottom, width, height, target left, target bottom)
+    let copies = [
+        (0, 0, width, height, x, y),
+        (0, 0, 1, height, x - 1, y),
+        (right, 0, 1, height, x + width, y),
+        (0, 0, width, 1, x, y - 1),
+        (0, top, width, 1, x, y + height),
+        (0, 0, 1, 1, x - 1, y - 1),
+        (right, 0, 1, 1, x + width, y - 1),
+        (0, top, 1, 1, x - 1, y + height),
+        (right, top, 1, 1, x + width, y + height),
+    ];
+
+    for (left, bottom, width, height, target_left, target_bottom) in copies {
+        source.blit_color(
+            &glium::Rect {
+                left,
+                bottom,
+                width,
+                height,
+            },
+            target,
+            &BlitTarget {
+                left: target_left,
+                bottom: target_bottom,
+                width: width as i32,
+                height: height as i32,
+            },
+            MagnifySamplerFilter::Nearest,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Page;
+
+    #[test]
+    fn freed_space_is_reused() {
+        let mut page = Page::default();
+        let (a, _, shelf) = page.allocate(10, 10, 30).unwrap();
+        let (b, _, _) = page.allocate(10, 10, 30).unwrap();
+        let (c, _, _) = page.allocate(10, 10, 30).unwrap();
+        assert_eq!((a, b, c), (0, 10, 20));
+        assert!(page.allocate(10, 30, 30).is_none());
+
+        // neighbouring spans merge, so a texture as wide as both fits
+        page.shelves[shelf].release(a, 10);
+        page.shelves[shelf].release(b, 10);
+        assert_eq!(page.allocate(20, 10, 30), Some((0, 0, shelf)));
+
+        page.shelves[shelf].release(c, 10);
+        assert_eq!(page.shelves[shelf].x, 20);
+    }
+}
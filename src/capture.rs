@@ -0,0 +1,41 @@
+use image::RgbaImage;
+
+/// Options controlling how `Overlay::capture` reads the window back.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOptions {
+    /// Keeps the alpha channel instead of making every pixel opaque. Overlay windows are
+    /// transparent wherever nothing is drawn, so only the overlay shows in the image.
+    pub alpha: bool,
+}
+
+impl CaptureOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn alpha(self, alpha: bool) -> Self {
+        Self { alpha }
+    }
+}
+
+/// Converts an image with premultiplied alpha, like renderers output, to the straight alpha
+/// image files expect.
+pub fn unpremultiply(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            pixel.0 = [0; 4];
+            continue;
+        }
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// Drops the alpha channel of an image with premultiplied alpha, which shows it over black.
+pub(crate) fn make_opaque(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        pixel[3] = 255;
+    }
+}
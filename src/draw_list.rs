@@ -0,0 +1,108 @@
+use std::rc::Rc;
+
+use crate::{
+    frame::{BlendMode, DrawCommand, Frame, Layer},
+    material::Material,
+    renderer::{Resource, ResourceHandle},
+    texture::Texture2D,
+    Rect, Vertex,
+};
+
+/// A batch of a draw list. Renderers upload it the first time they draw it and keep it until
+/// the draw list is recorded again or dropped.
+///
+/// Batches hold on to the textures they are drawn with, so they don't borrow the overlay
+/// the glyphs of their text come from.
+pub struct RetainedBatch {
+    /// Layer relative to the one the draw list is added to.
+    pub layer: Layer,
+    pub texture: Option<Texture2D>,
+    pub subpixel: bool,
+    pub clip: Option<Rect>,
+    pub material: Option<Rc<Material>>,
+    pub blend: BlendMode,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    resource: Resource,
+}
+
+impl RetainedBatch {
+    /// Returns a handle renderers key their copy of this batch by.
+    pub fn handle(&self) -> ResourceHandle {
+        self.resource.handle()
+    }
+}
+
+/// A set of primitives recorded once and kept by renderers between frames.
+///
+/// Adding a draw list to a frame costs no CPU time beyond a few commands, which makes it
+/// a good fit for static content like HUD chrome. The list is never updated on its own,
+/// call `invalidate` when its contents change and `record` it again.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// if !hud.is_valid() {
+///     let mut recording = overlay.new_frame();
+///     recording.add(Rectangle::new().dimensions([200.0, 100.0]));
+///     hud.record(recording);
+/// }
+/// frame.add_draw_list(&hud, [10.0, 10.0]);
+/// ```
+#[derive(Default)]
+pub struct DrawList {
+    batches: Vec<RetainedBatch>,
+    valid: bool,
+}
+
+impl DrawList {
+    /// Creates an empty draw list. It needs to be recorded before it draws anything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records everything added to `frame` into this draw list, replacing its previous contents.
+    ///
+    /// Layers, clip rectangles, transforms, alpha, tint, materials and blend modes active while
+    /// recording are baked in.
+    /// Draw lists and instanced shapes added to the recorded frame are not copied.
+    pub fn record(&mut self, mut frame: Frame<'_>) {
+        self.batches.clear();
+        for (layer, commands) in std::mem::take(&mut frame.layers) {
+            for command in commands {
+                let DrawCommand::Immediate(buffer) = command else {
+                    continue;
+                };
+                self.batches.push(RetainedBatch {
+                    layer,
+                    texture: buffer.texture.map(Texture2D::share),
+                    subpixel: buffer.subpixel,
+                    clip: buffer.clip,
+                    material: buffer.material,
+                    blend: buffer.blend,
+                    vertices: buffer.vertices,
+                    indices: buffer.indices,
+                    resource: Resource::new(),
+                });
+            }
+        }
+        self.valid = true;
+    }
+
+    /// Marks the draw list as outdated and lets renderers free their copies of it.
+    ///
+    /// An invalidated draw list draws nothing until it is recorded again.
+    pub fn invalidate(&mut self) {
+        self.batches.clear();
+        self.valid = false;
+    }
+
+    /// Returns whether the draw list was recorded and not invalidated since.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn batches(&self) -> &[RetainedBatch] {
+        &self.batches
+    }
+}
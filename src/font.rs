@@ -1,10 +1,106 @@
 use freetype_sys::{
-    FT_Done_Face, FT_Done_Library, FT_GlyphSlot, FT_Init_FreeType, FT_Load_Char, FT_New_Memory_Face, FT_Set_Char_Size, FT_LOAD_RENDER
+    FT_Done_Face, FT_Done_Library, FT_Err_Invalid_Argument, FT_Err_Invalid_File_Format,
+    FT_Err_Unknown_File_Format,
+    FT_Done_MM_Var, FT_Error, FT_Face, FT_Fixed, FT_Get_MM_Var, FT_Get_Var_Design_Coordinates,
+    FT_GlyphSlot, FT_Init_FreeType, FT_Int32, FT_Library, FT_Library_SetLcdFilter, FT_MM_Var,
+    FT_Set_Var_Design_Coordinates, FT_HAS_MULTIPLE_MASTERS,
+    FT_Load_Char, FT_New_Memory_Face, FT_Set_Char_Size, FT_Set_Transform, FT_Vector,
+    FT_LCD_FILTER_DEFAULT,
+    FT_LOAD_FORCE_AUTOHINT, FT_LOAD_NO_HINTING, FT_LOAD_RENDER, FT_LOAD_TARGET_LCD,
+    FT_LOAD_TARGET_LIGHT, FT_LOAD_TARGET_NORMAL,
 };
-use glium::{backend::Facade, texture::RawImage2d};
-
 use crate::texture::Texture2D;
 
+/// Largest glyph atlas side. Every GPU that runs OpenGL 3 supports textures this large.
+pub const MAX_ATLAS_SIZE: u32 = 8192;
+
+/// Smallest gamma applied to glyph coverage. Lower values, including zero and negative ones,
+/// are raised to it, since coverage is raised to the power of `1 / gamma`.
+pub const MIN_GAMMA: f32 = 0.01;
+
+#[derive(Debug)]
+pub enum FontError {
+    /// FreeType itself failed to initialize.
+    LibraryInitFailed(FT_Error),
+    /// The data is a font format FreeType doesn't support.
+    UnsupportedFormat,
+    /// The data could not be opened as a font face.
+    InvalidFace(FT_Error),
+    /// The requested face or named instance doesn't exist in the font file.
+    NoSuchFace { face_index: u32, named_instance: Option<u32> },
+    /// Variation axes were requested, but the face isn't a variable font.
+    NotVariable,
+    /// The face has no variation axis with this tag.
+    UnknownAxis([u8; 4]),
+    /// FreeType rejected the variation axis values.
+    InvalidVariation(FT_Error),
+    /// The font can't be rendered at the requested size.
+    InvalidSize(f32),
+    /// The glyph atlas exceeds `MAX_ATLAS_SIZE`.
+    AtlasTooLarge {
+        dimensions: (u32, u32),
+        max_size: u32,
+    },
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::LibraryInitFailed(code) => {
+                write!(f, "failed to initialize FreeType (error {})", code)
+            }
+            FontError::UnsupportedFormat => write!(f, "unsupported font format"),
+            FontError::InvalidFace(code) => write!(f, "invalid font face (FreeType error {})", code),
+            FontError::NoSuchFace {
+                face_index,
+                named_instance: Some(instance),
+            } => write!(f, "face {} has no named instance {}", face_index, instance),
+            FontError::NoSuchFace { face_index, .. } => {
+                write!(f, "font file has no face {}", face_index)
+            }
+            FontError::NotVariable => write!(f, "font face isn't a variable font"),
+            FontError::UnknownAxis(tag) => write!(
+                f,
+                "font face has no variation axis '{}'",
+                String::from_utf8_lossy(tag)
+            ),
+            FontError::InvalidVariation(code) => {
+                write!(f, "invalid variation axis values (FreeType error {})", code)
+            }
+            FontError::InvalidSize(size) => write!(f, "font can't be rendered at size {}", size),
+            FontError::AtlasTooLarge {
+                dimensions,
+                max_size,
+            } => write!(
+                f,
+                "glyph atlas of {}x{} exceeds the maximum texture size of {}",
+                dimensions.0, dimensions.1, max_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// Owns a FreeType library handle, releasing it on drop.
+pub(crate) struct Library(pub(crate) FT_Library);
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { FT_Done_Library(self.0) };
+    }
+}
+
+/// Owns a FreeType face handle, releasing it on drop.
+pub(crate) struct Face(pub(crate) FT_Face);
+
+impl Drop for Face {
+    fn drop(&mut self) {
+        unsafe { FT_Done_Face(self.0) };
+    }
+}
+
+#[derive(Default)]
 pub struct Glyph {
     pub advance_x: f32,
     pub advance_y: f32,
@@ -13,6 +109,155 @@ pub struct Glyph {
     pub bitmap_left: f32,
     pub bitmap_top: f32,
     pub texture_x: f32,
+    pub texture_y: f32,
+}
+
+/// How FreeType should hint glyph outlines before rasterizing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hinting {
+    /// No hinting, glyphs keep their exact outlines.
+    None,
+    /// Light hinting, only snaps vertically. Closest to how macOS renders text.
+    Light,
+    /// The font's own hinting instructions.
+    #[default]
+    Full,
+    /// FreeType's autohinter, ignoring the font's own instructions.
+    Auto,
+}
+
+/// Options controlling how a font is loaded and rasterized into its atlas.
+#[derive(Debug, Clone)]
+pub struct FontOptions {
+    pub hinting: Hinting,
+    /// Renders glyphs with LCD subpixel anti-aliasing (horizontal RGB).
+    pub subpixel: bool,
+    /// Gamma applied to glyph coverage. Values above 1.0 make text look bolder.
+    pub gamma: f32,
+    /// Contrast applied to glyph coverage around its midpoint. 1.0 leaves it untouched.
+    pub contrast: f32,
+    /// How many horizontal subpixel offsets each glyph is cached at, for subpixel positioning.
+    /// The atlas grows by as many copies of every glyph, so fonts only keep the one at offset 0
+    /// unless this is raised, usually to 3 or 4.
+    pub subpixel_positions: u32,
+    /// Index of the face to load from a font collection (.ttc/.otc).
+    pub face_index: u32,
+    /// Zero-based named instance of a variable font, e.g. "Bold" or "Condensed Light".
+    pub named_instance: Option<u32>,
+    /// Variable font axis values, by tag (`wght`, `wdth`, `slnt`, ...), in design units.
+    /// Applied on top of the named instance, if any.
+    pub variations: Vec<([u8; 4], f32)>,
+    /// Blends the glyphs as premultiplied alpha. Their coverage is in all four channels either
+    /// way, so it already is premultiplied, but is drawn as straight alpha by default.
+    /// Ignored for subpixel rendering.
+    pub premultiplied: bool,
+}
+
+impl Default for FontOptions {
+    fn default() -> Self {
+        Self {
+            hinting: Hinting::Full,
+            subpixel: false,
+            gamma: 1.0,
+            contrast: 1.0,
+            subpixel_positions: 1,
+            face_index: 0,
+            named_instance: None,
+            variations: Vec::new(),
+            premultiplied: false,
+        }
+    }
+}
+
+impl FontOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn hinting(self, hinting: Hinting) -> Self {
+        Self { hinting, ..self }
+    }
+
+    pub fn subpixel(self, subpixel: bool) -> Self {
+        Self { subpixel, ..self }
+    }
+
+    /// Sets the gamma applied to glyph coverage, clamped to at least `MIN_GAMMA`.
+    pub fn gamma(self, gamma: f32) -> Self {
+        Self {
+            gamma: gamma.max(MIN_GAMMA),
+            ..self
+        }
+    }
+
+    pub fn contrast(self, contrast: f32) -> Self {
+        Self { contrast, ..self }
+    }
+
+    pub fn subpixel_positions(self, subpixel_positions: u32) -> Self {
+        Self {
+            subpixel_positions: subpixel_positions.max(1),
+            ..self
+        }
+    }
+
+    pub fn face_index(self, face_index: u32) -> Self {
+        Self { face_index, ..self }
+    }
+
+    pub fn named_instance(self, named_instance: impl Into<Option<u32>>) -> Self {
+        Self {
+            named_instance: named_instance.into(),
+            ..self
+        }
+    }
+
+    pub fn premultiplied(self, premultiplied: bool) -> Self {
+        Self {
+            premultiplied,
+            ..self
+        }
+    }
+
+    /// Sets a variable font axis, replacing any previous value for the same tag.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use overlaylib::font::FontOptions;
+    /// let options = FontOptions::new().variation(b"wght", 650.0).variation(b"wdth", 87.5);
+    /// ```
+    pub fn variation(mut self, tag: &[u8; 4], value: f32) -> Self {
+        self.variations.retain(|(t, _)| t != tag);
+        self.variations.push((*tag, value));
+        self
+    }
+
+    /// Returns the FreeType load flags for these options.
+    pub fn load_flags(&self) -> FT_Int32 {
+        let hinting = match self.hinting {
+            Hinting::None => FT_LOAD_NO_HINTING,
+            Hinting::Light => FT_LOAD_TARGET_LIGHT,
+            Hinting::Full => FT_LOAD_TARGET_NORMAL,
+            Hinting::Auto => FT_LOAD_FORCE_AUTOHINT,
+        };
+        // the LCD target overrides the light/normal target bits
+        let target = if self.subpixel { FT_LOAD_TARGET_LCD } else { 0 };
+        FT_LOAD_RENDER | hinting | target
+    }
+
+    /// Maps a raw coverage value through the gamma and contrast adjustment.
+    fn adjust(&self, coverage: u8) -> u8 {
+        // keep fully transparent texels transparent no matter the contrast
+        if coverage == 0 {
+            return 0;
+        }
+        // the field is public, so it's clamped here too. NaN becomes MIN_GAMMA as well
+        let gamma = self.gamma.max(MIN_GAMMA);
+        let c = (coverage as f32 / 255.0).powf(1.0 / gamma);
+        let c = ((c - 0.5) * self.contrast + 0.5).clamp(0.0, 1.0);
+        (c * 255.0).round() as u8
+    }
 }
 
 pub struct Font {
@@ -20,13 +265,23 @@ pub struct Font {
 }
 
 impl Font {
-    pub fn new<F>(facade: &F, font_data: &[u8], font_size: f32) -> Self
-    where
-        F: ?Sized + Facade,
-    {
-        Self {
-            atlas: FontAtlas::new(facade, font_data, font_size),
-        }
+    pub fn new(font_data: &[u8], font_size: f32) -> Result<Self, FontError> {
+        Self::with_options(font_data, font_size, FontOptions::default())
+    }
+
+    pub fn with_options(
+        font_data: &[u8],
+        font_size: f32,
+        options: FontOptions,
+    ) -> Result<Self, FontError> {
+        Ok(Self {
+            atlas: FontAtlas::new(font_data, font_size, options)?,
+        })
+    }
+
+    /// Returns whether the glyphs were rendered with LCD subpixel anti-aliasing.
+    pub fn is_subpixel(&self) -> bool {
+        self.atlas.options.subpixel
     }
 
     pub fn get_glyph(&self, c: char) -> Option<&Glyph> {
@@ -43,108 +298,165 @@ pub struct FontAtlas {
     pub texture_dimensions: (u32, u32),
     pub font_size: f32,
     pub glyphs: Vec<Glyph>,
+    pub options: FontOptions,
 }
 
 impl FontAtlas {
-    pub fn new<F>(facade: &F, font_data: &[u8], font_size: f32) -> Self
-    where
-        F: ?Sized + Facade,
-    {
+    pub fn new(font_data: &[u8], font_size: f32, options: FontOptions) -> Result<Self, FontError> {
         let library = unsafe {
             let mut library = std::ptr::null_mut();
-            FT_Init_FreeType(&mut library);
-            library
+            let error = FT_Init_FreeType(&mut library);
+            if error != 0 {
+                return Err(FontError::LibraryInitFailed(error));
+            }
+            Library(library)
         };
-        
+
+        if options.subpixel {
+            // fails if FreeType was built without the LCD filter, the bitmaps are still usable then
+            unsafe { FT_Library_SetLcdFilter(library.0, FT_LCD_FILTER_DEFAULT) };
+        }
+
+        let load_flags = options.load_flags();
+        // LCD bitmaps store three horizontal subpixels per pixel
+        let subpixels = if options.subpixel { 3 } else { 1 };
+        let premultiplied = options.premultiplied && !options.subpixel;
+
         let face = unsafe {
             let mut face = std::ptr::null_mut();
-            FT_New_Memory_Face(
-                library,
+            // named instances are selected through the upper 16 bits, starting at 1
+            let instance = options.named_instance.map_or(0, |instance| instance + 1);
+            let error = FT_New_Memory_Face(
+                library.0,
                 font_data.as_ptr(),
                 font_data.len() as i64,
-                0,
+                ((instance as i64) << 16) | options.face_index as i64,
                 &mut face,
             );
-            face
+            match error {
+                0 => Face(face),
+                error
+                    if error == FT_Err_Unknown_File_Format
+                        || error == FT_Err_Invalid_File_Format =>
+                {
+                    return Err(FontError::UnsupportedFormat)
+                }
+                error if error == FT_Err_Invalid_Argument => {
+                    return Err(FontError::NoSuchFace {
+                        face_index: options.face_index,
+                        named_instance: options.named_instance,
+                    })
+                }
+                error => return Err(FontError::InvalidFace(error)),
+            }
         };
+        if !options.variations.is_empty() {
+            unsafe { set_variations(&library, &face, &options.variations)? };
+        }
         unsafe {
-            FT_Set_Char_Size(face, 0, (font_size * 64.0) as i64, 0, 0);
+            if FT_Set_Char_Size(face.0, 0, (font_size * 64.0) as i64, 0, 0) != 0 {
+                return Err(FontError::InvalidSize(font_size));
+            }
         }
 
-        let glyph: FT_GlyphSlot = unsafe { (*face).glyph };
+        let glyph: FT_GlyphSlot = unsafe { (*face.0).glyph };
+        let positions = options.subpixel_positions.max(1);
         let mut w = 0;
         let mut h = 0;
 
-        for i in 0..128 {
-            unsafe {
-                if FT_Load_Char(face, i as u64, FT_LOAD_RENDER) != 0 {
-                    println!("Failed to load char {}", i);
-                }
+        // every subpixel offset gets its own row in the atlas
+        for position in 0..positions {
+            let mut row_width = 0;
+            for i in 0..128 {
+                unsafe {
+                    set_subpixel_offset(face.0, position, positions);
+                    if FT_Load_Char(face.0, i as u64, load_flags) != 0 {
+                        continue;
+                    }
 
-                w += (*glyph).bitmap.width + 1;
-                h = h.max((*glyph).bitmap.rows);
+                    row_width += (*glyph).bitmap.width / subpixels + 1;
+                    h = h.max((*glyph).bitmap.rows);
+                }
             }
+            w = w.max(row_width);
         }
-        let mut image = vec![0u8; (w * h) as usize];
+        let row_height = h.max(1);
+        let w = w.max(1);
+        let h = row_height * positions as i32;
 
-        let mut x = 0;
+        if w as u32 > MAX_ATLAS_SIZE || h as u32 > MAX_ATLAS_SIZE {
+            return Err(FontError::AtlasTooLarge {
+                dimensions: (w as u32, h as u32),
+                max_size: MAX_ATLAS_SIZE,
+            });
+        }
 
-        let mut glyphs = Vec::with_capacity(128);
+        let mut image = vec![0u8; (w * h * 4) as usize];
 
-        for i in 0..128 {
-            unsafe {
-                if FT_Load_Char(face, i as u64, FT_LOAD_RENDER) != 0 {
-                    println!("Failed to load char {}", i);
-                }
+        let mut glyphs = Vec::with_capacity(128 * positions as usize);
 
-                let bitmap = &(*glyph).bitmap;
+        for position in 0..positions {
+            let mut x = 0;
+            let row = row_height * position as i32;
 
-                for y in 0..bitmap.rows {
-                    let src = bitmap.buffer.offset((y * bitmap.pitch) as isize);
-                    let dst = image.as_mut_ptr().offset((x + y * w) as isize);
-                    std::ptr::copy_nonoverlapping(src, dst, bitmap.width as usize);
-                }
+            for i in 0..128 {
+                unsafe {
+                    set_subpixel_offset(face.0, position, positions);
+                    if FT_Load_Char(face.0, i as u64, load_flags) != 0 {
+                        // characters the font can't load are drawn as nothing
+                        glyphs.push(Glyph::default());
+                        continue;
+                    }
 
-                glyphs.push(Glyph {
-                    advance_x: (*glyph).advance.x as f32 / 64.0,
-                    advance_y: (*glyph).advance.y as f32 / 64.0,
-                    bitmap_width: bitmap.width as f32,
-                    bitmap_height: bitmap.rows as f32,
-                    bitmap_left: (*glyph).bitmap_left as f32,
-                    bitmap_top: (*glyph).bitmap_top as f32,
-                    texture_x: x as f32 / w as f32,
-                });
-
-                x += bitmap.width + 1;
-            }
-        }
+                    let bitmap = &(*glyph).bitmap;
+                    let width = bitmap.width / subpixels;
 
-        let image = image
-            .chunks_exact(1)
-            .map(|chunk| {
-                [
-                    *chunk.first().unwrap(),
-                    *chunk.first().unwrap(),
-                    *chunk.first().unwrap(),
-                    *chunk.first().unwrap(),
-                ]
-            })
-            .flatten()
-            .collect::<Vec<_>>();
+                    for y in 0..bitmap.rows {
+                        let src = std::slice::from_raw_parts(
+                            bitmap.buffer.offset((y * bitmap.pitch) as isize),
+                            bitmap.width as usize,
+                        );
+                        for px in 0..width {
+                            let dst = (((x + px) + (row + y) * w) * 4) as usize;
+                            let texel = if options.subpixel {
+                                let r = options.adjust(src[(px * 3) as usize]);
+                                let g = options.adjust(src[(px * 3 + 1) as usize]);
+                                let b = options.adjust(src[(px * 3 + 2) as usize]);
+                                [r, g, b, r.max(g).max(b)]
+                            } else {
+                                let c = options.adjust(src[px as usize]);
+                                [c, c, c, c]
+                            };
+                            image[dst..dst + 4].copy_from_slice(&texel);
+                        }
+                    }
 
-        let image = RawImage2d::from_raw_rgba(image, (w as u32, h as u32));
+                    glyphs.push(Glyph {
+                        advance_x: (*glyph).advance.x as f32 / 64.0,
+                        advance_y: (*glyph).advance.y as f32 / 64.0,
+                        bitmap_width: width as f32,
+                        bitmap_height: bitmap.rows as f32,
+                        bitmap_left: (*glyph).bitmap_left as f32,
+                        bitmap_top: (*glyph).bitmap_top as f32,
+                        texture_x: x as f32 / w as f32,
+                        texture_y: row as f32 / h as f32,
+                    });
 
-        let texture = Texture2D::new(glium::texture::Texture2d::new(facade, image).unwrap(), (w as u32, h as u32));
+                    x += width + 1;
+                }
+            }
+        }
 
-        unsafe { FT_Done_Face(face) };
-        unsafe { FT_Done_Library(library) };
+        let mut texture = Texture2D::new(image, (w as u32, h as u32));
+        texture.premultiplied = premultiplied;
 
-        Self {
+        Ok(Self {
             texture,
             texture_dimensions: (w as u32, h as u32),
-            font_size: font_size as f32,
-            glyphs
-        }
+            font_size,
+            glyphs,
+            options,
+        })
     }
 
     pub fn get_glyph(&self, c: char) -> Option<&Glyph> {
@@ -156,4 +468,118 @@ impl FontAtlas {
 
         self.glyphs.get(index)
     }
+
+    /// Returns the glyph rasterized closest to the given horizontal subpixel offset.
+    ///
+    /// `offset` is the fractional part of the pen position, in the range `0.0..1.0`.
+    pub fn get_glyph_at(&self, c: char, offset: f32) -> Option<&Glyph> {
+        let index = c as usize;
+
+        if index >= 128 {
+            return None;
+        }
+
+        let positions = self.options.subpixel_positions.max(1);
+        let position = (offset.rem_euclid(1.0) * positions as f32).round() as u32 % positions;
+
+        self.glyphs.get(position as usize * 128 + index)
+    }
+}
+
+/// Shifts the outline of subsequently loaded glyphs by `position / positions` of a pixel.
+unsafe fn set_subpixel_offset(face: FT_Face, position: u32, positions: u32) {
+    let mut delta = FT_Vector {
+        x: (position * 64 / positions) as i64,
+        y: 0,
+    };
+    FT_Set_Transform(face, std::ptr::null_mut(), &mut delta);
+}
+
+/// Sets the design coordinates of the given variation axes, keeping the others as they are.
+unsafe fn set_variations(
+    library: &Library,
+    face: &Face,
+    variations: &[([u8; 4], f32)],
+) -> Result<(), FontError> {
+    if !FT_HAS_MULTIPLE_MASTERS(face.0) {
+        return Err(FontError::NotVariable);
+    }
+
+    // freetype-sys declares the out parameter as `*mut FT_MM_Var`, but FreeType writes a pointer to it
+    let mut master: *mut FT_MM_Var = std::ptr::null_mut();
+    let error = FT_Get_MM_Var(face.0, &mut master as *mut *mut FT_MM_Var as *mut FT_MM_Var);
+    if error != 0 || master.is_null() {
+        return Err(FontError::NotVariable);
+    }
+
+    let axes = std::slice::from_raw_parts((*master).axis, (*master).num_axis as usize);
+    let mut coords: Vec<FT_Fixed> = axes.iter().map(|axis| axis.def).collect();
+    // start from the current coordinates so a named instance isn't reset to the defaults
+    FT_Get_Var_Design_Coordinates(face.0, coords.len() as u32, coords.as_mut_ptr());
+
+    let mut result = Ok(());
+    for (tag, value) in variations {
+        let tag_value = u32::from_be_bytes(*tag) as u64;
+        match axes.iter().position(|axis| axis.tag == tag_value) {
+            Some(index) => {
+                let axis = &axes[index];
+                let value = (*value as f64 * 65536.0) as FT_Fixed;
+                coords[index] = value.clamp(axis.minimum, axis.maximum);
+            }
+            None => {
+                result = Err(FontError::UnknownAxis(*tag));
+                break;
+            }
+        }
+    }
+
+    FT_Done_MM_Var(library.0, master);
+    result?;
+
+    match FT_Set_Var_Design_Coordinates(face.0, coords.len() as u32, coords.as_ptr()) {
+        0 => Ok(()),
+        error => Err(FontError::InvalidVariation(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FontOptions, MIN_GAMMA};
+
+    #[test]
+    fn adjust_gamma_and_contrast() {
+        let neutral = FontOptions::new();
+        for coverage in [0, 1, 64, 128, 200, 255] {
+            assert_eq!(neutral.adjust(coverage), coverage);
+        }
+
+        // gamma above 1 brightens partial coverage, below 1 darkens it
+        let bold = FontOptions::new().gamma(2.0);
+        assert_eq!(bold.adjust(64), 128);
+        assert_eq!(bold.adjust(255), 255);
+        let thin = FontOptions::new().gamma(0.5);
+        assert_eq!(thin.adjust(128), 64);
+
+        // contrast pushes coverage away from the midpoint, without touching empty texels
+        let sharp = FontOptions::new().contrast(2.0);
+        assert_eq!(sharp.adjust(64), 1);
+        assert_eq!(sharp.adjust(200), 255);
+        assert_eq!(sharp.adjust(0), 0);
+    }
+
+    #[test]
+    fn adjust_invalid_gamma() {
+        for gamma in [0.0, -1.0] {
+            assert_eq!(FontOptions::new().gamma(gamma).gamma, MIN_GAMMA);
+        }
+        for gamma in [0.0, -1.0, f32::NAN, f32::NEG_INFINITY] {
+            let options = FontOptions {
+                gamma,
+                ..FontOptions::new()
+            };
+            assert_eq!(options.adjust(0), 0);
+            assert_eq!(options.adjust(128), 0);
+            assert_eq!(options.adjust(255), 255);
+        }
+    }
 }
@@ -1,109 +1,592 @@
+use std::{collections::BTreeMap, rc::Rc, time::Instant};
+
 use crate::{
-    primitives::{text::Text, Primitive, PrimitiveType, Rectangle},
+    draw_list::{DrawList, RetainedBatch},
+    material::Material,
+    math::{self, Matrix4x4},
+    primitives::{Geometry, Instanced, Primitive, Resolver, ShapeInstance},
+    stats::FrameStats,
     texture::Texture2D,
-    Overlay, Vertex,
+    Color, Overlay, Rect, Vertex,
 };
 
+#[derive(Default)]
 pub struct TexturedBuffer<'a> {
     pub texture: Option<&'a Texture2D>,
     pub vertices: Vec<Vertex>,
+    /// Indices into `vertices`, three per triangle.
+    pub indices: Vec<u32>,
+    /// Whether the texture holds LCD subpixel coverage rather than plain alpha.
+    pub subpixel: bool,
+    /// Scissor rectangle in screen coordinates. Nothing outside of it is drawn.
+    pub clip: Option<Rect>,
+    /// Custom shader to draw with, instead of the built-in ones.
+    pub material: Option<Rc<Material>>,
+    pub blend: BlendMode,
 }
 
 impl<'a> TexturedBuffer<'a> {
     pub fn with_texture(texture: &'a Texture2D) -> Self {
         Self {
             texture: Some(texture),
-            vertices: Vec::new(),
+            ..Self::new()
         }
     }
 
-    pub fn with_texture_and_buffer(texture: &'a Texture2D, vertices: Vec<Vertex>) -> Self {
+    pub fn with_texture_and_geometry(texture: &'a Texture2D, geometry: Geometry) -> Self {
         Self {
             texture: Some(texture),
-            vertices,
+            ..Self::with_geometry(geometry)
         }
     }
 
-    pub fn with_buffer(vertices: Vec<Vertex>) -> Self {
+    pub fn with_geometry(geometry: Geometry) -> Self {
         Self {
-            texture: None,
-            vertices,
+            vertices: geometry.vertices,
+            indices: geometry.indices,
+            ..Self::new()
         }
     }
 
     pub fn new() -> Self {
-        Self {
-            texture: None,
-            vertices: Vec::new(),
-        }
+        Default::default()
+    }
+
+    /// Appends the geometry of `other`, offsetting its indices past the existing vertices.
+    fn append(&mut self, other: &TexturedBuffer<'a>) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + base));
+    }
+
+    /// Returns whether the vertices of `other` can be drawn in the same draw call as this buffer.
+    fn can_merge(&self, other: &TexturedBuffer<'a>) -> bool {
+        self.texture == other.texture
+            && self.subpixel == other.subpixel
+            && self.clip == other.clip
+            && self.material == other.material
+            && self.blend == other.blend
+    }
+}
+
+/// Shapes drawn with GPU instancing, in a single draw call.
+#[derive(Default)]
+pub struct InstanceBuffer {
+    pub instances: Vec<ShapeInstance>,
+    /// Scissor rectangle in screen coordinates. Nothing outside of it is drawn.
+    pub clip: Option<Rect>,
+    pub blend: BlendMode,
+}
+
+/// How the colors of a primitive are combined with what's already drawn below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// Regular alpha blending, where opaque colors replace what's below them.
+    #[default]
+    Normal,
+    /// Adds the color to what's below it, brightening it. Good for glows and highlights.
+    Additive,
+    /// Multiplies what's below by the color, darkening it. Good for shadows and vignettes.
+    Multiply,
+    /// The inverse of multiply, brightening what's below without ever exceeding white.
+    Screen,
+    /// Alpha blending for vertex colors whose color channels are already multiplied by their alpha.
+    /// Textures say whether they're premultiplied themselves, see `Texture2D::premultiplied`.
+    Premultiplied,
+}
+
+/// The depth a primitive is drawn at. Higher layers are drawn on top of lower ones,
+/// regardless of the order primitives were added in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Layer(pub i32);
+
+impl Layer {
+    pub const BACKGROUND: Layer = Layer(-200);
+    pub const WORLD: Layer = Layer(-100);
+    /// The layer primitives go to when no other layer was pushed.
+    pub const DEFAULT: Layer = Layer(0);
+    pub const HUD: Layer = Layer(100);
+    pub const TOOLTIP: Layer = Layer(200);
+}
+
+impl From<i32> for Layer {
+    fn from(value: i32) -> Self {
+        Layer(value)
     }
 }
 
+/// A single draw call worth of geometry.
+pub enum DrawCommand<'a> {
+    /// Vertices built this frame.
+    Immediate(TexturedBuffer<'a>),
+    /// Rectangles and circles generated on the GPU.
+    Instanced(InstanceBuffer),
+    /// A batch of a retained draw list, which renderers keep a copy of between frames.
+    Retained {
+        batch: &'a RetainedBatch,
+        offset: [f32; 2],
+        clip: Option<Rect>,
+    },
+}
+
 pub struct Frame<'a> {
-    pub buffers: Vec<TexturedBuffer<'a>>,
+    /// Draw commands of every layer, in the order they are drawn in.
+    pub layers: BTreeMap<Layer, Vec<DrawCommand<'a>>>,
     pub overlay: &'a Overlay,
+    layer_stack: Vec<Layer>,
+    clip_stack: Vec<Rect>,
+    transform: Matrix4x4,
+    transform_stack: Vec<Matrix4x4>,
+    alpha_stack: Vec<f32>,
+    tint_stack: Vec<Color>,
+    material_stack: Vec<Rc<Material>>,
+    blend_stack: Vec<BlendMode>,
+    anti_aliasing: bool,
+    stats: FrameStats,
 }
 
 impl<'a> Frame<'a> {
     pub fn new(overlay: &'a Overlay) -> Self {
         Self {
-            buffers: vec![],
+            layers: BTreeMap::new(),
             overlay,
+            layer_stack: vec![],
+            clip_stack: vec![],
+            transform: Matrix4x4::identity(),
+            transform_stack: vec![],
+            alpha_stack: vec![],
+            tint_stack: vec![],
+            material_stack: vec![],
+            blend_stack: vec![],
+            anti_aliasing: false,
+            stats: FrameStats::default(),
         }
     }
 
     pub fn clear(&mut self) {
-        self.buffers.clear();
+        self.layers.clear();
+        self.layer_stack.clear();
+        self.clip_stack.clear();
+        self.transform = Matrix4x4::identity();
+        self.transform_stack.clear();
+        self.alpha_stack.clear();
+        self.tint_stack.clear();
+        self.material_stack.clear();
+        self.blend_stack.clear();
+        self.stats = FrameStats::default();
     }
 
-    fn add_buffer(&mut self, buffer: TexturedBuffer<'a>) {
-        if self.buffers.len() == 0 {
-            self.buffers.push(buffer);
-            return;
+    /// Returns what was added to the frame since it was created or cleared, and how long
+    /// building it took. Only the renderer fills in the draw calls, uploads and submit time.
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Returns every draw command, from the bottom layer to the top one.
+    pub fn commands(&self) -> impl Iterator<Item = &DrawCommand<'a>> {
+        self.layers.values().flatten()
+    }
+
+    /// Pushes a layer onto the layer stack. Everything added afterwards is drawn on that layer.
+    pub fn push_layer(&mut self, layer: impl Into<Layer>) {
+        self.layer_stack.push(layer.into());
+    }
+
+    /// Pops a layer from the layer stack.
+    ///
+    /// Returns the layer that was popped, or None if the stack was empty.
+    pub fn pop_layer(&mut self) -> Option<Layer> {
+        self.layer_stack.pop()
+    }
+
+    /// Returns the layer primitives are currently added to.
+    pub fn current_layer(&self) -> Layer {
+        self.layer_stack.last().copied().unwrap_or_default()
+    }
+
+    /// Adds a primitive to the given layer, without changing the current layer.
+    pub fn add_to_layer(&mut self, layer: impl Into<Layer>, shape: impl Primitive<'a>) {
+        self.push_layer(layer);
+        self.add(shape);
+        self.pop_layer();
+    }
+
+    /// Pushes a clip rectangle onto the clip stack.
+    ///
+    /// The rectangle is in screen coordinates, it is not affected by the transform stack.
+    /// Everything added afterwards is clipped to the rectangle, intersected with the enclosing clip rectangle.
+    /// Primitives entirely outside of it are discarded without being sent to the GPU.
+    pub fn push_clip_rect(&mut self, rect: Rect) {
+        let rect = match self.clip_stack.last() {
+            Some(&current) => math::rect_intersection(current, rect),
+            None => rect,
+        };
+        self.clip_stack.push(rect);
+    }
+
+    /// Pops a clip rectangle from the clip stack.
+    ///
+    /// Returns the clip rectangle that was popped, or None if the stack was empty.
+    pub fn pop_clip_rect(&mut self) -> Option<Rect> {
+        self.clip_stack.pop()
+    }
+
+    /// Returns the current clip rectangle, or None if nothing is clipped.
+    pub fn current_clip_rect(&self) -> Option<Rect> {
+        self.clip_stack.last().copied()
+    }
+
+    /// Saves the current transform onto the transform stack.
+    ///
+    /// Calls to `translate`, `rotate` and `scale` afterwards can be undone with `pop_transform`.
+    pub fn push_transform(&mut self) {
+        self.transform_stack.push(self.transform);
+    }
+
+    /// Restores the transform saved by the last `push_transform`.
+    ///
+    /// Returns the transform that was active before popping, or None if the stack was empty.
+    pub fn pop_transform(&mut self) -> Option<Matrix4x4> {
+        let saved = self.transform_stack.pop()?;
+        Some(std::mem::replace(&mut self.transform, saved))
+    }
+
+    /// Returns the transform applied to every vertex added to the frame.
+    pub fn current_transform(&self) -> Matrix4x4 {
+        self.transform
+    }
+
+    /// Moves the origin of subsequently added primitives by `offset`, in local coordinates.
+    pub fn translate(&mut self, offset: impl Into<[f32; 2]>) {
+        let offset = offset.into();
+        self.transform = Matrix4x4::translate(offset[0], offset[1], 0.0) * self.transform;
+    }
+
+    /// Rotates subsequently added primitives clockwise around the local origin, by `angle` radians.
+    pub fn rotate(&mut self, angle: f32) {
+        // the screen's y axis points down, so a clockwise rotation is around -z
+        self.transform = Matrix4x4::rotate(angle, [0.0, 0.0, -1.0]) * self.transform;
+    }
+
+    /// Scales subsequently added primitives around the local origin.
+    pub fn scale(&mut self, scale: impl Into<[f32; 2]>) {
+        let scale = scale.into();
+        self.transform = Matrix4x4::scale(scale[0], scale[1], 1.0) * self.transform;
+    }
+
+    /// Pushes an opacity onto the alpha stack.
+    ///
+    /// The alpha of everything added afterwards is multiplied by it, and by every alpha below it on the stack.
+    pub fn push_alpha(&mut self, alpha: f32) {
+        let alpha = alpha * self.current_alpha();
+        self.alpha_stack.push(alpha);
+    }
+
+    /// Pops an opacity from the alpha stack.
+    ///
+    /// Returns the combined alpha that was popped, or None if the stack was empty.
+    pub fn pop_alpha(&mut self) -> Option<f32> {
+        self.alpha_stack.pop()
+    }
+
+    /// Returns the combined opacity applied to added primitives.
+    pub fn current_alpha(&self) -> f32 {
+        self.alpha_stack.last().copied().unwrap_or(1.0)
+    }
+
+    /// Pushes a tint onto the tint stack.
+    ///
+    /// The color of everything added afterwards is multiplied by it, and by every tint below it on the stack.
+    pub fn push_tint(&mut self, tint: impl Into<Color>) {
+        let tint = tint.into();
+        let current = self.current_tint();
+        self.tint_stack.push(std::array::from_fn(|i| tint[i] * current[i]));
+    }
+
+    /// Pops a tint from the tint stack.
+    ///
+    /// Returns the combined tint that was popped, or None if the stack was empty.
+    pub fn pop_tint(&mut self) -> Option<Color> {
+        self.tint_stack.pop()
+    }
+
+    /// Returns the combined tint applied to added primitives.
+    pub fn current_tint(&self) -> Color {
+        self.tint_stack.last().copied().unwrap_or([1.0; 4])
+    }
+
+    /// Pushes a material onto the material stack. Everything added afterwards is drawn with its shaders.
+    ///
+    /// The material has to be registered with `Overlay::add_material` before the frame is drawn.
+    /// Instanced shapes added while a material is active are drawn from vertices instead.
+    pub fn push_material(&mut self, material: Material) {
+        self.material_stack.push(Rc::new(material));
+    }
+
+    /// Pops a material from the material stack.
+    ///
+    /// Returns the material that was popped, or None if the stack was empty.
+    pub fn pop_material(&mut self) -> Option<Rc<Material>> {
+        self.material_stack.pop()
+    }
+
+    /// Returns the material primitives are currently drawn with, or None for the built-in shaders.
+    pub fn current_material(&self) -> Option<&Material> {
+        self.material_stack.last().map(|material| &**material)
+    }
+
+    /// Adds a primitive drawn with `material`, without changing the current material.
+    pub fn add_with_material(&mut self, material: Material, shape: impl Primitive<'a>) {
+        self.push_material(material);
+        self.add(shape);
+        self.pop_material();
+    }
+
+    /// Pushes a blend mode onto the blend stack. Everything added afterwards is blended with it.
+    pub fn push_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_stack.push(mode);
+    }
+
+    /// Pops a blend mode from the blend stack.
+    ///
+    /// Returns the blend mode that was popped, or None if the stack was empty.
+    pub fn pop_blend_mode(&mut self) -> Option<BlendMode> {
+        self.blend_stack.pop()
+    }
+
+    /// Returns the blend mode primitives are currently added with.
+    pub fn current_blend_mode(&self) -> BlendMode {
+        self.blend_stack.last().copied().unwrap_or_default()
+    }
+
+    /// Adds a primitive blended with `mode`, without changing the current blend mode.
+    pub fn add_with_blend_mode(&mut self, mode: BlendMode, shape: impl Primitive<'a>) {
+        self.push_blend_mode(mode);
+        self.add(shape);
+        self.pop_blend_mode();
+    }
+
+    /// Enables or disables anti-aliasing of shape edges. It's disabled by default.
+    ///
+    /// Untextured primitives added while it's enabled get a one pixel wide fringe fading to
+    /// transparent around their outline, which smooths their edges without multisampling.
+    /// The fringe is centered on the outline, so lines and borders a pixel thin look fainter.
+    pub fn set_anti_aliasing(&mut self, enabled: bool) {
+        self.anti_aliasing = enabled;
+    }
+
+    /// Returns whether shape edges are anti-aliased.
+    pub fn anti_aliasing(&self) -> bool {
+        self.anti_aliasing
+    }
+
+    /// Returns the factors vertex colors are multiplied by: the tint, with the alpha applied.
+    fn modulation(&self, blend: BlendMode) -> [f32; 4] {
+        let mut modulate = self.current_tint();
+        modulate[3] *= self.current_alpha();
+        if blend == BlendMode::Premultiplied {
+            let alpha = modulate[3];
+            for channel in &mut modulate[..3] {
+                *channel *= alpha;
+            }
+        }
+        modulate
+    }
+
+    fn add_buffer(&mut self, mut buffer: TexturedBuffer<'a>) {
+        if self.transform.data != Matrix4x4::identity().data {
+            for vertex in &mut buffer.vertices {
+                vertex.position = self.transform.transform_point(vertex.position);
+            }
+        }
+
+        let blend = self.current_blend_mode();
+
+        // after transforming, so the fringe is a pixel wide on screen
+        if self.anti_aliasing && buffer.texture.is_none() {
+            let outline = buffer.vertices.len();
+            math::feather_edges(&mut buffer.vertices, &mut buffer.indices, 1.0);
+            if blend == BlendMode::Premultiplied {
+                // transparent premultiplied colors are black, or the fringe would glow
+                for vertex in &mut buffer.vertices[outline..] {
+                    vertex.color = [0.0; 4];
+                }
+            }
         }
-        let len = self.buffers.len();
-        if self.buffers[len - 1].texture == buffer.texture {
-            self.buffers[len - 1].vertices.extend_from_slice(&buffer.vertices);
-        } else {
-            self.buffers.push(buffer);
-        }
-    }
-
-    pub fn add(&mut self, shape: impl Primitive) {
-        let shape = Box::new(shape);
-        match shape.get_type() {
-            PrimitiveType::Text => {
-                let mut text: Box<Text> = unsafe { std::mem::transmute(shape) }; // a necessary evil, PRs welcome
-                if text.font.is_none() {
-                    text.font = Some(
-                        self.overlay
-                            .current_font()
-                            .expect("No font on the stack"),
-                    );
+
+        let modulate = self.modulation(blend);
+        if modulate != [1.0; 4] {
+            for vertex in &mut buffer.vertices {
+                for (channel, factor) in vertex.color.iter_mut().zip(modulate) {
+                    *channel *= factor;
                 }
-                let Some(font) = text.font else {return;};
-                let buffer = TexturedBuffer::with_texture_and_buffer(
-                    font.get_texture(),
-                    text.get_vertices(),
-                );
-                self.add_buffer(buffer);
             }
-            PrimitiveType::Rectangle => {
-                let rect: Box<Rectangle> = unsafe { std::mem::transmute(shape) }; // a necessary evil, PRs welcome
-                let buffer = match rect.texture {
-                    Some(texture) => {
-                        TexturedBuffer::with_texture_and_buffer(texture, rect.get_vertices())
-                    },
-                    None => {
-                        TexturedBuffer::with_buffer(rect.get_vertices())
-                    }
-                };
-                self.add_buffer(buffer);
+        }
+
+        buffer.clip = self.current_clip_rect();
+        buffer.material = self.material_stack.last().cloned();
+        buffer.blend = blend;
+        if let Some(clip) = buffer.clip {
+            let visible = math::bounding_rect(&buffer.vertices)
+                .map(|bounds| !math::rect_is_empty(math::rect_intersection(bounds, clip)))
+                .unwrap_or(false);
+            if !visible {
+                return;
+            }
+        }
+
+        self.stats.vertices += buffer.vertices.len();
+        let commands = self.layers.entry(self.current_layer()).or_default();
+        match commands.last_mut() {
+            Some(DrawCommand::Immediate(last)) if last.can_merge(&buffer) => {
+                last.append(&buffer);
+            }
+            _ => commands.push(DrawCommand::Immediate(buffer)),
+        }
+    }
+
+    /// Adds a retained draw list to the frame, moved by `offset`.
+    ///
+    /// The list's layers are relative to the current layer, and its clip rectangles are
+    /// intersected with the current one. The transform, alpha and tint stacks don't apply,
+    /// since the vertices are not touched on the CPU.
+    pub fn add_draw_list(&mut self, list: &'a DrawList, offset: impl Into<[f32; 2]>) {
+        let offset = offset.into();
+        let current_layer = self.current_layer();
+        let current_clip = self.current_clip_rect();
+
+        for batch in list.batches() {
+            let recorded_clip = batch.clip.map(|[min, max]| {
+                [
+                    [min[0] + offset[0], min[1] + offset[1]],
+                    [max[0] + offset[0], max[1] + offset[1]],
+                ]
+            });
+            let clip = match (recorded_clip, current_clip) {
+                (Some(a), Some(b)) => Some(math::rect_intersection(a, b)),
+                (a, b) => a.or(b),
+            };
+            if clip.is_some_and(math::rect_is_empty) {
+                continue;
+            }
+
+            let layer = Layer(current_layer.0 + batch.layer.0);
+            self.layers
+                .entry(layer)
+                .or_default()
+                .push(DrawCommand::Retained {
+                    batch,
+                    offset,
+                    clip,
+                });
+        }
+    }
+
+    /// Adds a rectangle or circle drawn with GPU instancing.
+    ///
+    /// Instead of building vertices, the shape is sent as a single `ShapeInstance` and its edges
+    /// are anti-aliased on the GPU, which makes thousands of boxes per frame cheap. Falls back to
+    /// `add` for shapes that can't be instanced, like textured rectangles, under a rotating or
+    /// non-uniformly scaling transform, or while a material is pushed.
+    ///
+    /// Renderers without hardware instancing expand every instance into a quad on their own.
+    pub fn add_instanced(&mut self, shape: impl Primitive<'a> + Instanced) {
+        let start = Instant::now();
+        self.stats.primitives += 1;
+        self.add_instance(shape);
+        self.stats.build_time += start.elapsed();
+    }
+
+    fn add_instance(&mut self, shape: impl Primitive<'a> + Instanced) {
+        let instance = shape
+            .get_instance()
+            .filter(|_| self.material_stack.is_empty())
+            .and_then(|instance| self.transform_instance(instance));
+        let Some(mut instance) = instance else {
+            self.add_shape(shape);
+            return;
+        };
+
+        let blend = self.current_blend_mode();
+        let modulate = self.modulation(blend);
+        for color in [&mut instance.color, &mut instance.border_color] {
+            for (channel, factor) in color.iter_mut().zip(modulate) {
+                *channel *= factor;
             }
-            _ => {
-                let buffer = TexturedBuffer::with_buffer(shape.get_vertices());
-                self.add_buffer(buffer);
+        }
+
+        let clip = self.current_clip_rect();
+        if let Some(clip) = clip {
+            let [x, y] = instance.origin;
+            let [w, h] = instance.size;
+            let bounds = [[x, y], [x + w, y + h]];
+            if math::rect_is_empty(math::rect_intersection(bounds, clip)) {
+                return;
+            }
+        }
+
+        self.stats.instances += 1;
+        let commands = self.layers.entry(self.current_layer()).or_default();
+        match commands.last_mut() {
+            Some(DrawCommand::Instanced(last)) if last.clip == clip && last.blend == blend => {
+                last.instances.push(instance);
             }
+            _ => commands.push(DrawCommand::Instanced(InstanceBuffer {
+                instances: vec![instance],
+                clip,
+                blend,
+            })),
+        }
+    }
+
+    /// Applies the current transform to an instance.
+    ///
+    /// Returns None if the transform rotates, shears or scales unevenly, which instances can't represent.
+    fn transform_instance(&self, instance: ShapeInstance) -> Option<ShapeInstance> {
+        let data = self.transform.data;
+        let (scale_x, scale_y) = (data[0][0], data[1][1]);
+        if data[0][1] != 0.0 || data[1][0] != 0.0 || scale_x.abs() != scale_y.abs() {
+            return None;
+        }
+
+        let [x, y] = instance.origin;
+        let [w, h] = instance.size;
+        let a = self.transform.transform_point([x, y]);
+        let b = self.transform.transform_point([x + w, y + h]);
+        let scale = scale_x.abs();
+        Some(ShapeInstance {
+            origin: [a[0].min(b[0]), a[1].min(b[1])],
+            size: [(b[0] - a[0]).abs(), (b[1] - a[1]).abs()],
+            radius: instance.radius * scale,
+            border: instance.border * scale,
+            ..instance
+        })
+    }
+
+    /// Adds a primitive to the current layer.
+    pub fn add(&mut self, shape: impl Primitive<'a>) {
+        let start = Instant::now();
+        self.stats.primitives += 1;
+        self.add_shape(shape);
+        self.stats.build_time += start.elapsed();
+    }
+
+    fn add_shape(&mut self, mut shape: impl Primitive<'a>) {
+        shape.resolve(&Resolver::new(self.overlay));
+
+        let geometry = shape.get_geometry();
+        if geometry.is_empty() {
+            return;
         }
+        let buffer = match (shape.get_texture(), shape.get_font()) {
+            (Some(texture), _) => TexturedBuffer::with_texture_and_geometry(texture, geometry),
+            (None, Some(font)) => TexturedBuffer {
+                subpixel: font.is_subpixel(),
+                ..TexturedBuffer::with_texture_and_geometry(font.get_texture(), geometry)
+            },
+            (None, None) => TexturedBuffer::with_geometry(geometry),
+        };
+        self.add_buffer(buffer);
     }
 }
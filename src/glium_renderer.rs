@@ -0,0 +1,1365 @@
+use std::{cell::Cell, collections::HashMap, mem, ops::Range, rc::Rc, time::Instant};
+
+use glium::{
+    backend::{Context, Facade},
+    implement_vertex,
+    index::{IndexBuffer, IndicesSource, NoIndices, PrimitiveType},
+    program,
+    texture::{MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::Uniforms,
+    vertex::{VertexBuffer, VerticesSource},
+    DrawParameters, Surface,
+};
+use image::RgbaImage;
+
+use crate::{
+    atlas::{AtlasRegion, AtlasVertex, TextureAtlas},
+    frame::{BlendMode, DrawCommand, Frame, InstanceBuffer, TexturedBuffer},
+    material::{self, Material, MaterialUniforms, MaterialValue},
+    math,
+    primitives::ShapeInstance,
+    renderer::{Renderer, ResourceHandle},
+    stats::FrameStats,
+    texture::Texture2D,
+    OverlayError, Rect, Vertex,
+};
+
+/// A corner of the unit quad every shape instance is stretched from.
+#[derive(Copy, Clone)]
+struct Corner {
+    corner: [f32; 2],
+}
+
+implement_vertex!(Corner, corner);
+
+/// A corner of a shape instance, for GPUs that can't draw instances. Every instance is then
+/// drawn from four of them instead.
+#[derive(Copy, Clone)]
+struct InstanceCorner {
+    corner: [f32; 2],
+    origin: [f32; 2],
+    size: [f32; 2],
+    color: [f32; 4],
+    border_color: [f32; 4],
+    radius: f32,
+    border: f32,
+}
+
+implement_vertex!(
+    InstanceCorner,
+    corner,
+    origin,
+    size,
+    color,
+    border_color,
+    radius,
+    border
+);
+
+/// What's needed to draw `ShapeInstance`s.
+struct Instancing {
+    program: glium::Program,
+    quad: VertexBuffer<Corner>,
+    /// Whether attribute divisors are supported. Instances are expanded into vertices otherwise.
+    per_instance: bool,
+}
+
+/// A copy of a texture or draw list, kept until the original is dropped.
+struct Cached<T> {
+    handle: ResourceHandle,
+    value: T,
+}
+
+/// Key of the texture atlas when counting texture switches, which no `Texture2D::id` reaches.
+const ATLAS_TEXTURE: u64 = u64::MAX;
+
+/// The vertices and indices of a retained batch.
+type RetainedBuffers = (VertexBuffer<Vertex>, IndexBuffer<u32>);
+
+/// GPU copies of the textures drawn so far, keyed by `Texture2D::id`.
+#[derive(Default)]
+pub(crate) struct GpuTextures(HashMap<u64, Cached<glium::Texture2d>>);
+
+impl GpuTextures {
+    pub fn get(&self, texture: &Texture2D) -> Option<&glium::Texture2d> {
+        self.0.get(&texture.id()).map(|cached| &cached.value)
+    }
+
+    /// Uploads a texture that isn't on the GPU yet. Returns the number of bytes uploaded.
+    fn upload(
+        &mut self,
+        context: &Rc<Context>,
+        texture: &Texture2D,
+    ) -> Result<usize, OverlayError> {
+        if self.0.contains_key(&texture.id()) {
+            return Ok(0);
+        }
+        // render targets are only created by `GliumRenderer::render_to_texture`
+        let Some(pixels) = texture.pixels() else {
+            return Ok(0);
+        };
+        let bytes = pixels.len();
+        let image = RawImage2d::from_raw_rgba(pixels.to_vec(), texture.dimensions());
+        let value = glium::Texture2d::new(context, image)
+            .map_err(|_| OverlayError::TextureCreationError)?;
+        self.insert(texture, value);
+        Ok(bytes)
+    }
+
+    fn insert(&mut self, texture: &Texture2D, value: glium::Texture2d) {
+        let handle = texture.handle();
+        self.0.insert(handle.id(), Cached { handle, value });
+    }
+
+    /// Uploads the texture a batch is drawn with, along with those its material samples.
+    ///
+    /// Returns the number of bytes uploaded.
+    fn upload_batch(
+        &mut self,
+        context: &Rc<Context>,
+        texture: Option<&Texture2D>,
+        material: Option<&Material>,
+    ) -> Result<usize, OverlayError> {
+        let mut bytes = 0;
+        if let Some(texture) = texture {
+            bytes += self.upload(context, texture)?;
+        }
+        for (_, value) in material.iter().flat_map(|material| &material.uniforms) {
+            if let MaterialValue::Texture(texture) = value {
+                bytes += self.upload(context, texture)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Frees the copies of textures that were dropped.
+    fn purge(&mut self) {
+        self.0.retain(|_, cached| cached.handle.is_alive());
+    }
+}
+
+/// Vertex and index storage for immediate geometry, reused across frames.
+///
+/// Every frame's geometry is written into a single dynamic vertex buffer and index buffer,
+/// which are only reallocated (to the next power of two) when a frame doesn't fit anymore.
+#[derive(Default)]
+struct GeometryPool {
+    vertex_buffer: Option<VertexBuffer<AtlasVertex>>,
+    index_buffer: Option<IndexBuffer<u32>>,
+    vertices: Vec<AtlasVertex>,
+    indices: Vec<u32>,
+    /// Every immediate command staged this frame, in order.
+    staged: Vec<Staged>,
+    instance_buffer: Option<VertexBuffer<ShapeInstance>>,
+    instances: Vec<ShapeInstance>,
+    /// Range in `instances` of every instanced command staged this frame.
+    instance_ranges: Vec<Range<usize>>,
+    /// The instances expanded into four corners each, when the GPU can't draw instances.
+    corner_buffer: Option<VertexBuffer<InstanceCorner>>,
+    corner_index_buffer: Option<IndexBuffer<u32>>,
+}
+
+struct Staged {
+    /// Range in `indices`.
+    indices: Range<usize>,
+    /// Whether the texture coordinates were moved into the atlas.
+    atlased: bool,
+}
+
+impl GeometryPool {
+    const MIN_CAPACITY: usize = 4096;
+
+    fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.staged.clear();
+        self.instances.clear();
+        self.instance_ranges.clear();
+    }
+
+    /// Stages a batch, rebasing its indices onto the shared vertex buffer.
+    ///
+    /// With a `region`, texture coordinates are moved to where its texture sits in the atlas.
+    fn stage(&mut self, buffer: &TexturedBuffer, region: Option<AtlasRegion>) {
+        let base = self.vertices.len() as u32;
+        let start = self.indices.len();
+        match region {
+            Some(region) => self
+                .vertices
+                .extend(buffer.vertices.iter().map(|vertex| region.map(vertex))),
+            None => self
+                .vertices
+                .extend(buffer.vertices.iter().map(|vertex| AtlasVertex {
+                    position: vertex.position,
+                    tex_coords: vertex.tex_coords,
+                    color: vertex.color,
+                    layer: 0.0,
+                    tex_premultiplied: 0.0,
+                })),
+        }
+        self.indices
+            .extend(buffer.indices.iter().map(|index| index + base));
+        self.staged.push(Staged {
+            indices: start..self.indices.len(),
+            atlased: region.is_some(),
+        });
+    }
+
+    fn stage_instances(&mut self, buffer: &InstanceBuffer) {
+        let start = self.instances.len();
+        self.instances.extend_from_slice(&buffer.instances);
+        self.instance_ranges.push(start..self.instances.len());
+    }
+
+    /// Uploads the staged geometry, growing the GPU buffers if needed.
+    ///
+    /// With `expand_instances`, every instance is uploaded as four vertices and six indices
+    /// instead of a single instance. Returns the number of bytes uploaded.
+    fn upload<F: ?Sized + Facade>(
+        &mut self,
+        facade: &F,
+        expand_instances: bool,
+    ) -> Result<usize, OverlayError> {
+        let mut bytes = 0;
+        if expand_instances {
+            let mut corners = Vec::with_capacity(self.instances.len() * 4);
+            let mut indices = Vec::with_capacity(self.instances.len() * 6);
+            for instance in &self.instances {
+                let base = corners.len() as u32;
+                for corner in [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]] {
+                    corners.push(InstanceCorner {
+                        corner,
+                        origin: instance.origin,
+                        size: instance.size,
+                        color: instance.color,
+                        border_color: instance.border_color,
+                        radius: instance.radius,
+                        border: instance.border,
+                    });
+                }
+                indices.extend([0, 1, 2, 2, 1, 3].map(|index| base + index));
+            }
+            bytes += write_vertices(facade, &mut self.corner_buffer, &corners)?;
+            bytes += write_indices(facade, &mut self.corner_index_buffer, &indices)?;
+        } else {
+            bytes += write_vertices(facade, &mut self.instance_buffer, &self.instances)?;
+        }
+
+        bytes += write_vertices(facade, &mut self.vertex_buffer, &self.vertices)?;
+        bytes += write_indices(facade, &mut self.index_buffer, &self.indices)?;
+        Ok(bytes)
+    }
+}
+
+fn capacity(len: usize) -> usize {
+    len.next_power_of_two().max(GeometryPool::MIN_CAPACITY)
+}
+
+/// Writes `data` to the start of a dynamic vertex buffer, replacing it first if it's too small.
+///
+/// Returns the number of bytes written.
+fn write_vertices<F, T>(
+    facade: &F,
+    buffer: &mut Option<VertexBuffer<T>>,
+    data: &[T],
+) -> Result<usize, OverlayError>
+where
+    F: ?Sized + Facade,
+    T: glium::Vertex,
+{
+    if data.is_empty() {
+        return Ok(0);
+    }
+    if data.len() > buffer.as_ref().map_or(0, |buffer| buffer.len()) {
+        *buffer = Some(
+            VertexBuffer::empty_dynamic(facade, capacity(data.len()))
+                .map_err(|_| OverlayError::BufferCreationError)?,
+        );
+    }
+    let buffer = buffer.as_ref().ok_or(OverlayError::BufferCreationError)?;
+    // orphan the old contents so the driver doesn't wait for the previous frame to finish
+    buffer.invalidate();
+    buffer
+        .slice(0..data.len())
+        .ok_or(OverlayError::BufferCreationError)?
+        .write(data);
+    Ok(mem::size_of_val(data))
+}
+
+/// Writes `data` to the start of a dynamic index buffer, replacing it first if it's too small.
+///
+/// Returns the number of bytes written.
+fn write_indices<F>(
+    facade: &F,
+    buffer: &mut Option<IndexBuffer<u32>>,
+    data: &[u32],
+) -> Result<usize, OverlayError>
+where
+    F: ?Sized + Facade,
+{
+    if data.is_empty() {
+        return Ok(0);
+    }
+    if data.len() > buffer.as_ref().map_or(0, |buffer| buffer.len()) {
+        *buffer = Some(
+            IndexBuffer::empty_dynamic(facade, PrimitiveType::TrianglesList, capacity(data.len()))
+                .map_err(|_| OverlayError::BufferCreationError)?,
+        );
+    }
+    let buffer = buffer.as_ref().ok_or(OverlayError::BufferCreationError)?;
+    buffer.invalidate();
+    buffer
+        .slice(0..data.len())
+        .ok_or(OverlayError::BufferCreationError)?
+        .write(data);
+    Ok(mem::size_of_val(data))
+}
+
+/// Everything needed to issue one draw call.
+struct Batch<'b> {
+    vertices: VerticesSource<'b>,
+    indices: IndicesSource<'b>,
+    texture: Option<&'b Texture2D>,
+    subpixel: bool,
+    material: Option<&'b Material>,
+    blend: BlendMode,
+    scissor: Option<glium::Rect>,
+}
+
+/// Consecutive atlased commands that can be drawn together.
+struct AtlasRun {
+    /// Range in the staged indices.
+    indices: Range<usize>,
+    scissor: Option<glium::Rect>,
+    blend: BlendMode,
+}
+
+/// Draws frames with OpenGL, through glium.
+///
+/// Shapes, text and small textures are copied into a shared texture atlas and drawn together,
+/// so consecutive commands only need separate draw calls when their clip rectangles differ.
+/// Textures and draw lists are uploaded the first time they are drawn, and freed once they are dropped.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let overlay = Overlay::new()?;
+/// let mut renderer = GliumRenderer::new(&display)?;
+///
+/// let mut frame = overlay.new_frame();
+/// frame.add(Rectangle::new().dimensions([100.0, 100.0]));
+///
+/// let mut target = display.draw();
+/// renderer.render(&mut target, &frame)?;
+/// target.finish()?;
+/// ```
+pub struct GliumRenderer {
+    context: Rc<Context>,
+    texture_program: glium::Program,
+    shape_program: glium::Program,
+    /// Dual-source blending program for LCD subpixel text. Requires GLSL 3.30, so it's optional.
+    subpixel_program: Option<glium::Program>,
+    /// Samples the texture atlas, so shapes and packed textures share draw calls.
+    atlas_program: glium::Program,
+    instancing: Instancing,
+    /// Programs added with `add_material`, by name.
+    materials: HashMap<String, glium::Program>,
+    geometry_pool: GeometryPool,
+    atlas: TextureAtlas,
+    textures: GpuTextures,
+    /// GPU copies of the retained batches drawn so far, keyed by `RetainedBatch::handle`.
+    retained: HashMap<u64, Cached<RetainedBuffers>>,
+    /// Statistics of the frame being drawn, or of the last one once it's drawn.
+    stats: Cell<FrameStats>,
+    /// Texture sampled by the previous draw call, to count texture switches.
+    bound_texture: Cell<Option<u64>>,
+}
+
+impl GliumRenderer {
+    /// Compiles the programs the overlay is drawn with.
+    pub fn new<F>(facade: &F) -> Result<Self, OverlayError>
+    where
+        F: ?Sized + Facade,
+    {
+        let texture_program = program!(facade,
+            140 => {
+                vertex: "
+                #version 140
+
+                in vec2 position;
+                in vec2 tex_coords;
+                in vec4 color;
+
+                out vec4 v_color;
+                out vec2 v_tex_coords;
+
+                uniform mat4 projection;
+
+                void main() {
+                    gl_Position = projection * vec4(position, 0.0, 1.0);
+                    v_color = color;
+                    v_tex_coords = tex_coords;
+                }
+                ",
+                fragment: "
+                #version 140
+
+                in vec4 v_color;
+                in vec2 v_tex_coords;
+
+                out vec4 color;
+                uniform sampler2D tex;
+                uniform bool premultiplied;
+                uniform bool tex_premultiplied;
+
+                void main() {
+                    vec4 texel = texture(tex, v_tex_coords);
+                    vec4 tint = v_color;
+                    if (!tex_premultiplied) {
+                        texel.rgb *= texel.a;
+                    }
+                    if (!premultiplied) {
+                        tint.rgb *= tint.a;
+                    }
+                    color = texel * tint;
+                }
+                "
+            },
+        )
+        .map_err(|_| OverlayError::ShaderCompilationError)?;
+
+        let shape_program = program!(facade,
+            140 => {
+                vertex: "
+                #version 140
+
+                in vec2 position;
+                in vec2 tex_coords;
+                in vec4 color;
+
+                out vec4 v_color;
+                out vec2 v_tex_coords;
+
+                uniform mat4 projection;
+
+                void main() {
+                    gl_Position = projection * vec4(position, 0.0, 1.0);
+                    v_color = color;
+                    v_tex_coords = tex_coords;
+                }
+                ",
+                fragment: "
+                #version 140
+
+                in vec4 v_color;
+                in vec2 v_tex_coords;
+
+                out vec4 color;
+                uniform bool premultiplied;
+
+                void main() {
+                    color = v_color;
+                    if (!premultiplied) {
+                        color.rgb *= color.a;
+                    }
+                }
+                "
+            },
+        )
+        .map_err(|_| OverlayError::ShaderCompilationError)?;
+
+        let subpixel_program = program!(facade,
+            330 => {
+                vertex: "
+                #version 330
+
+                in vec2 position;
+                in vec2 tex_coords;
+                in vec4 color;
+
+                out vec4 v_color;
+                out vec2 v_tex_coords;
+
+                uniform mat4 projection;
+
+                void main() {
+                    gl_Position = projection * vec4(position, 0.0, 1.0);
+                    v_color = color;
+                    v_tex_coords = tex_coords;
+                }
+                ",
+                fragment: "
+                #version 330
+
+                in vec4 v_color;
+                in vec2 v_tex_coords;
+
+                layout(location = 0, index = 0) out vec4 color;
+                layout(location = 0, index = 1) out vec4 blend_weights;
+                uniform sampler2D tex;
+
+                void main() {
+                    vec4 coverage = texture(tex, v_tex_coords);
+                    color = vec4(v_color.rgb, 1.0);
+                    blend_weights = coverage * v_color.a;
+                }
+                "
+            },
+        )
+        .ok();
+
+        let atlas_program = program!(facade,
+            140 => {
+                vertex: "
+                #version 140
+
+                in vec2 position;
+                in vec2 tex_coords;
+                in vec4 color;
+                in float layer;
+                in float tex_premultiplied;
+
+                out vec4 v_color;
+                out vec2 v_tex_coords;
+                out float v_layer;
+                out float v_tex_premultiplied;
+
+                uniform mat4 projection;
+
+                void main() {
+                    gl_Position = projection * vec4(position, 0.0, 1.0);
+                    v_color = color;
+                    v_tex_coords = tex_coords;
+                    v_layer = layer;
+                    v_tex_premultiplied = tex_premultiplied;
+                }
+                ",
+                fragment: "
+                #version 140
+
+                in vec4 v_color;
+                in vec2 v_tex_coords;
+                in float v_layer;
+                in float v_tex_premultiplied;
+
+                out vec4 color;
+                uniform sampler2DArray tex;
+                uniform bool premultiplied;
+
+                void main() {
+                    vec4 texel = texture(tex, vec3(v_tex_coords, v_layer));
+                    vec4 tint = v_color;
+                    // pages hold both kinds of textures, so this comes with every vertex
+                    if (v_tex_premultiplied < 0.5) {
+                        texel.rgb *= texel.a;
+                    }
+                    if (!premultiplied) {
+                        tint.rgb *= tint.a;
+                    }
+                    color = texel * tint;
+                }
+                "
+            },
+        )
+        .map_err(|_| OverlayError::ShaderCompilationError)?;
+
+        let instancing = Self::create_instancing(facade)?;
+
+        Ok(Self {
+            context: facade.get_context().clone(),
+            texture_program,
+            shape_program,
+            subpixel_program,
+            atlas_program,
+            instancing,
+            materials: HashMap::new(),
+            geometry_pool: GeometryPool::default(),
+            atlas: TextureAtlas::default(),
+            textures: GpuTextures::default(),
+            retained: HashMap::new(),
+            stats: Cell::default(),
+            bound_texture: Cell::default(),
+        })
+    }
+
+    /// Compiles the instanced shape program.
+    fn create_instancing<F>(facade: &F) -> Result<Instancing, OverlayError>
+    where
+        F: ?Sized + Facade,
+    {
+        let program = program!(facade,
+            140 => {
+                vertex: "
+                #version 140
+
+                in vec2 corner;
+                in vec2 origin;
+                in vec2 size;
+                in vec4 color;
+                in vec4 border_color;
+                in float radius;
+                in float border;
+
+                out vec2 v_local;
+                out vec2 v_half_size;
+                out vec4 v_color;
+                out vec4 v_border_color;
+                out float v_radius;
+                out float v_border;
+
+                uniform mat4 projection;
+
+                void main() {
+                    vec2 half_size = size * 0.5;
+                    // one pixel of margin around the shape for its anti-aliased edge
+                    vec2 local = (corner * 2.0 - 1.0) * (half_size + 1.0);
+                    gl_Position = projection * vec4(origin + half_size + local, 0.0, 1.0);
+                    v_local = local;
+                    v_half_size = half_size;
+                    v_color = color;
+                    v_border_color = border_color;
+                    v_radius = min(radius, min(half_size.x, half_size.y));
+                    v_border = border;
+                }
+                ",
+                fragment: "
+                #version 140
+
+                in vec2 v_local;
+                in vec2 v_half_size;
+                in vec4 v_color;
+                in vec4 v_border_color;
+                in float v_radius;
+                in float v_border;
+
+                out vec4 color;
+                uniform bool premultiplied;
+
+                // distance to the edge of a rounded rectangle, negative inside
+                float rounded_box(vec2 p, vec2 half_size, float radius) {
+                    vec2 q = abs(p) - half_size + radius;
+                    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+                }
+
+                void main() {
+                    float distance = rounded_box(v_local, v_half_size, v_radius);
+                    float outer = clamp(0.5 - distance, 0.0, 1.0);
+                    float inner = clamp(0.5 - distance - v_border, 0.0, 1.0);
+
+                    vec4 fill = v_color;
+                    vec4 border = v_border_color;
+                    if (!premultiplied) {
+                        fill.rgb *= fill.a;
+                        border.rgb *= border.a;
+                    }
+                    color = fill * inner + border * (outer - inner);
+                    if (color.a <= 0.0) {
+                        discard;
+                    }
+                }
+                "
+            },
+        )
+        .map_err(|_| OverlayError::ShaderCompilationError)?;
+
+        let quad = VertexBuffer::immutable(
+            facade,
+            &[
+                Corner { corner: [0.0, 0.0] },
+                Corner { corner: [1.0, 0.0] },
+                Corner { corner: [0.0, 1.0] },
+                Corner { corner: [1.0, 1.0] },
+            ],
+        )
+        .map_err(|_| OverlayError::BufferCreationError)?;
+
+        // attribute divisors need OpenGL 3.3 or an extension, glium reports which
+        let per_instance = VertexBuffer::<ShapeInstance>::empty(facade, 1)
+            .map_err(|_| OverlayError::BufferCreationError)?
+            .per_instance()
+            .is_ok();
+
+        Ok(Instancing {
+            program,
+            quad,
+            per_instance,
+        })
+    }
+
+    /// Returns whether the GPU draws `ShapeInstance`s with hardware instancing. Without it,
+    /// every instance is expanded into a quad on the CPU.
+    pub fn supports_instancing(&self) -> bool {
+        self.instancing.per_instance
+    }
+
+    /// Adds a material, drawn with the built-in vertex shader and the given fragment shader.
+    ///
+    /// The fragment shader gets `v_color`, `v_tex_coords` and `v_position` from the vertex shader
+    /// (see `material::DEFAULT_VERTEX_SHADER`), and can declare these uniforms besides its own:
+    ///
+    /// * `projection` - The orthographic projection of the target.
+    /// * `resolution` - The target's size in pixels.
+    /// * `tex` - The primitive's texture, for textured primitives and text.
+    /// * `tex_premultiplied` - Whether `tex` has premultiplied alpha.
+    ///
+    /// The output color must be premultiplied by its alpha, which is what the blend modes expect.
+    ///
+    /// Adding a material with the name of an existing one replaces it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The material name. Used to reference the material in `material::Material`.
+    /// * `fragment_shader` - GLSL source of the fragment shader, version 140 or later.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// renderer.add_material("scanlines", "
+    ///     #version 140
+    ///     in vec4 v_color;
+    ///     in vec2 v_position;
+    ///     out vec4 color;
+    ///     uniform float time;
+    ///
+    ///     void main() {
+    ///         color = v_color * (0.75 + 0.25 * sin(v_position.y + time * 10.0));
+    ///         color.rgb *= color.a;
+    ///     }
+    /// ")?;
+    /// ```
+    pub fn add_material(&mut self, name: &str, fragment_shader: &str) -> Result<(), OverlayError> {
+        self.add_material_with_vertex_shader(name, material::DEFAULT_VERTEX_SHADER, fragment_shader)
+    }
+
+    /// Adds a material with its own vertex shader.
+    ///
+    /// The vertex shader gets the `position`, `tex_coords` and `color` attributes of every vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The material name. Used to reference the material in `material::Material`.
+    /// * `vertex_shader` - GLSL source of the vertex shader, version 140 or later.
+    /// * `fragment_shader` - GLSL source of the fragment shader.
+    pub fn add_material_with_vertex_shader(
+        &mut self,
+        name: &str,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<(), OverlayError> {
+        let program =
+            glium::Program::from_source(&self.context, vertex_shader, fragment_shader, None)
+                .map_err(|_| OverlayError::ShaderCompilationError)?;
+        self.materials.insert(name.to_string(), program);
+        Ok(())
+    }
+
+    /// Returns whether a material called `name` was added.
+    pub fn has_material(&self, name: &str) -> bool {
+        self.materials.contains_key(name)
+    }
+
+    /// Removes every texture from the texture atlas.
+    ///
+    /// Textures are copied into the atlas the first time they are drawn, and their space is
+    /// reused once they are dropped. Rows of the atlas keep the height of the first texture
+    /// packed into them though, so call this to repack it when the textures drawn change a lot.
+    pub fn clear_atlas(&mut self) {
+        self.atlas.clear();
+    }
+
+    /// Draws `frame` into a render target created with `Texture2D::render_target`, replacing
+    /// its contents.
+    ///
+    /// Frames drawn by this renderer afterwards can draw the texture like any other, to cache
+    /// complex widgets or to post-process with a material. Drawing the texture into itself
+    /// draws nothing.
+    pub fn render_to_texture(
+        &mut self,
+        texture: &Texture2D,
+        frame: &Frame<'_>,
+    ) -> Result<(), OverlayError> {
+        if !texture.is_render_target() {
+            return Err(OverlayError::NotRenderTarget);
+        }
+        // taken out of the cache while drawn into, so that the frame can't sample it
+        let gl_texture = match self.textures.0.remove(&texture.id()) {
+            Some(cached) => cached.value,
+            None => {
+                let (width, height) = texture.dimensions();
+                glium::Texture2d::empty_with_format(
+                    &self.context,
+                    UncompressedFloatFormat::U8U8U8U8,
+                    MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                )
+                .map_err(|_| OverlayError::TextureCreationError)?
+            }
+        };
+
+        let mut surface = gl_texture.as_surface();
+        surface.clear_color(0.0, 0.0, 0.0, 0.0);
+        // the first row of a texture is at texture coordinate 0, which primitives draw at the top
+        let result = self.draw(&mut surface, frame, true);
+        drop(surface);
+
+        self.textures.insert(texture, gl_texture);
+        result
+    }
+
+    /// Reads back the front buffer, which holds what was last presented in the window.
+    ///
+    /// The image starts with its top row and has premultiplied alpha.
+    pub fn read_front_buffer(&self) -> Result<RgbaImage, OverlayError> {
+        let raw: RawImage2d<u8> = self
+            .context
+            .read_front_buffer()
+            .map_err(OverlayError::ReadError)?;
+        let image = RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())
+            .expect("read back an RGBA pixel per texel");
+        // the first row of a framebuffer is its bottom
+        Ok(image::imageops::flip_vertical(&image))
+    }
+
+    /// Reads back this renderer's copy of a texture, like a render target drawn into with
+    /// `render_to_texture`.
+    ///
+    /// The image starts with the row at texture coordinate 0, which is the top of what was
+    /// drawn into render targets. Returns None if the texture wasn't uploaded or drawn into yet.
+    pub fn read_texture(&self, texture: &Texture2D) -> Option<RgbaImage> {
+        let raw: RawImage2d<u8> = self.textures.get(texture)?.read();
+        Some(
+            RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())
+                .expect("read back an RGBA pixel per texel"),
+        )
+    }
+
+    /// Uploads everything `frame` draws, and stages its immediate geometry.
+    fn prepare(&mut self, frame: &Frame<'_>) -> Result<(), OverlayError> {
+        self.textures.purge();
+        self.atlas.purge();
+        self.retained.retain(|_, cached| cached.handle.is_alive());
+
+        let mut upload_bytes = 0;
+        let pool = &mut self.geometry_pool;
+        pool.clear();
+        for command in frame.commands() {
+            match command {
+                DrawCommand::Immediate(buffer) => {
+                    upload_bytes += self.textures.upload_batch(
+                        &self.context,
+                        buffer.texture,
+                        buffer.material.as_deref(),
+                    )?;
+                    let source = buffer.texture.and_then(|texture| self.textures.get(texture));
+                    pool.stage(buffer, self.atlas.resolve(&self.context, buffer, source));
+                }
+                DrawCommand::Instanced(buffer) => pool.stage_instances(buffer),
+                DrawCommand::Retained { batch, .. } => {
+                    upload_bytes += self.textures.upload_batch(
+                        &self.context,
+                        batch.texture.as_ref(),
+                        batch.material.as_deref(),
+                    )?;
+                    let handle = batch.handle();
+                    if self.retained.contains_key(&handle.id()) {
+                        continue;
+                    }
+                    let vertices = VertexBuffer::immutable(&self.context, &batch.vertices)
+                        .map_err(|_| OverlayError::BufferCreationError)?;
+                    let indices = IndexBuffer::immutable(
+                        &self.context,
+                        PrimitiveType::TrianglesList,
+                        &batch.indices,
+                    )
+                    .map_err(|_| OverlayError::BufferCreationError)?;
+                    upload_bytes += mem::size_of_val(&batch.vertices[..])
+                        + mem::size_of_val(&batch.indices[..]);
+                    self.retained.insert(
+                        handle.id(),
+                        Cached {
+                            handle,
+                            value: (vertices, indices),
+                        },
+                    );
+                }
+            }
+        }
+        upload_bytes += pool.upload(&self.context, !self.instancing.per_instance)?;
+        self.stats.get_mut().upload_bytes += upload_bytes;
+        Ok(())
+    }
+
+    /// Returns the statistics of the last frame drawn, including what was added to it.
+    pub fn stats(&self) -> FrameStats {
+        self.stats.get()
+    }
+
+    /// Counts a draw call sampling `texture`, or no texture.
+    fn count_draw_call(&self, texture: Option<u64>) {
+        let mut stats = self.stats.get();
+        stats.draw_calls += 1;
+        if texture.is_some() && texture != self.bound_texture.get() {
+            stats.texture_switches += 1;
+            self.bound_texture.set(texture);
+        }
+        self.stats.set(stats);
+    }
+}
+
+impl<S: Surface> Renderer<S> for GliumRenderer {
+    type Error = OverlayError;
+
+    fn render(&mut self, target: &mut S, frame: &Frame<'_>) -> Result<(), OverlayError> {
+        self.draw(target, frame, false)
+    }
+}
+
+impl GliumRenderer {
+    /// Draws `frame` on `target`. Flipped, the top of the frame goes to the first row of the target.
+    fn draw<S: Surface>(
+        &mut self,
+        target: &mut S,
+        frame: &Frame<'_>,
+        flipped: bool,
+    ) -> Result<(), OverlayError> {
+        let start = Instant::now();
+        self.stats.set(*frame.stats());
+        self.bound_texture.set(None);
+
+        let result = self.draw_commands(target, frame, flipped);
+        self.stats.get_mut().submit_time = start.elapsed();
+        result
+    }
+
+    fn draw_commands<S: Surface>(
+        &mut self,
+        target: &mut S,
+        frame: &Frame<'_>,
+        flipped: bool,
+    ) -> Result<(), OverlayError> {
+        self.prepare(frame)?;
+
+        let (width, height) = target.get_dimensions();
+        let (bottom, top) = if flipped {
+            (0.0, height as f32)
+        } else {
+            (height as f32, 0.0)
+        };
+        let projection = math::Matrix4x4::orthographic(0.0, width as f32, bottom, top, -1.0, 1.0);
+        let scissor_rect = |clip| scissor_rect(clip, (width, height), flipped);
+
+        // atlased commands are drawn lazily, so that consecutive ones go out in one draw call
+        let pool = &self.geometry_pool;
+        let mut pending: Option<AtlasRun> = None;
+        let mut staged = pool.staged.iter();
+        let mut instance_ranges = pool.instance_ranges.iter();
+        for command in frame.commands() {
+            match command {
+                DrawCommand::Immediate(buffer) => {
+                    let Some(staged) = staged.next() else {
+                        continue;
+                    };
+                    let scissor = buffer.clip.map(scissor_rect);
+                    if staged.atlased {
+                        match &mut pending {
+                            Some(run)
+                                if run.scissor == scissor
+                                    && run.blend == buffer.blend
+                                    && run.indices.end == staged.indices.start =>
+                            {
+                                run.indices.end = staged.indices.end;
+                            }
+                            _ => {
+                                if let Some(run) = pending.take() {
+                                    self.draw_atlased(target, run, projection)?;
+                                }
+                                pending = Some(AtlasRun {
+                                    indices: staged.indices.clone(),
+                                    scissor,
+                                    blend: buffer.blend,
+                                });
+                            }
+                        }
+                        continue;
+                    }
+
+                    if let Some(run) = pending.take() {
+                        self.draw_atlased(target, run, projection)?;
+                    }
+                    let (Some(vertex_buffer), Some(index_buffer)) =
+                        (&pool.vertex_buffer, &pool.index_buffer)
+                    else {
+                        continue;
+                    };
+                    let Some(indices) = index_buffer.slice(staged.indices.clone()) else {
+                        continue;
+                    };
+                    let batch = Batch {
+                        vertices: vertex_buffer.into(),
+                        indices: indices.into(),
+                        texture: buffer.texture,
+                        subpixel: buffer.subpixel,
+                        material: buffer.material.as_deref(),
+                        blend: buffer.blend,
+                        scissor,
+                    };
+                    self.draw_batch(target, batch, projection)?;
+                }
+                DrawCommand::Instanced(buffer) => {
+                    if let Some(run) = pending.take() {
+                        self.draw_atlased(target, run, projection)?;
+                    }
+                    let Some(range) = instance_ranges.next() else {
+                        continue;
+                    };
+                    let scissor = buffer.clip.map(scissor_rect);
+                    self.draw_instances(target, range.clone(), scissor, buffer.blend, projection)?;
+                }
+                DrawCommand::Retained {
+                    batch,
+                    offset,
+                    clip,
+                } => {
+                    if let Some(run) = pending.take() {
+                        self.draw_atlased(target, run, projection)?;
+                    }
+                    let Some(cached) = self.retained.get(&batch.handle().id()) else {
+                        continue;
+                    };
+                    let (vertices, indices) = &cached.value;
+                    // moving the projection is what lets the vertices stay untouched
+                    let projection =
+                        math::Matrix4x4::translate(offset[0], offset[1], 0.0) * projection;
+                    let batch = Batch {
+                        vertices: vertices.into(),
+                        indices: indices.into(),
+                        texture: batch.texture.as_ref(),
+                        subpixel: batch.subpixel,
+                        material: batch.material.as_deref(),
+                        blend: batch.blend,
+                        scissor: clip.map(scissor_rect),
+                    };
+                    self.draw_batch(target, batch, projection)?;
+                }
+            }
+        }
+        if let Some(run) = pending {
+            self.draw_atlased(target, run, projection)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws a range of the staged indices whose texture coordinates point into the atlas.
+    fn draw_atlased<T: Surface>(
+        &self,
+        target: &mut T,
+        run: AtlasRun,
+        projection: math::Matrix4x4,
+    ) -> Result<(), OverlayError> {
+        let pool = &self.geometry_pool;
+        let (Some(vertex_buffer), Some(index_buffer), Some(texture)) =
+            (&pool.vertex_buffer, &pool.index_buffer, self.atlas.texture())
+        else {
+            return Ok(());
+        };
+        let Some(indices) = index_buffer.slice(run.indices) else {
+            return Ok(());
+        };
+
+        let tex = texture
+            .sampled()
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp);
+        let uniforms = uniform! {
+            projection: projection.data,
+            tex: tex,
+            premultiplied: run.blend == BlendMode::Premultiplied,
+        };
+        let batch = Batch {
+            vertices: vertex_buffer.into(),
+            indices: indices.into(),
+            texture: None,
+            subpixel: false,
+            material: None,
+            blend: run.blend,
+            scissor: run.scissor,
+        };
+        self.count_draw_call(Some(ATLAS_TEXTURE));
+        self.submit(target, batch, &self.atlas_program, &uniforms, blending(run.blend))
+    }
+
+    /// Draws a range of the staged shape instances.
+    fn draw_instances<T: Surface>(
+        &self,
+        target: &mut T,
+        range: Range<usize>,
+        scissor: Option<glium::Rect>,
+        blend: BlendMode,
+        projection: math::Matrix4x4,
+    ) -> Result<(), OverlayError> {
+        let pool = &self.geometry_pool;
+        let instancing = &self.instancing;
+        let uniforms = uniform! {
+            projection: projection.data,
+            premultiplied: blend == BlendMode::Premultiplied,
+        };
+        let parameters = DrawParameters {
+            blend: blending(blend),
+            scissor,
+            ..Default::default()
+        };
+
+        if !instancing.per_instance {
+            // every instance was expanded into a quad of four corners and six indices
+            let (Some(corner_buffer), Some(index_buffer)) =
+                (&pool.corner_buffer, &pool.corner_index_buffer)
+            else {
+                return Ok(());
+            };
+            let Some(indices) = index_buffer.slice(range.start * 6..range.end * 6) else {
+                return Ok(());
+            };
+            self.count_draw_call(None);
+            return target
+                .draw(corner_buffer, indices, &instancing.program, &uniforms, &parameters)
+                .map_err(OverlayError::GliumError);
+        }
+
+        let Some(instance_buffer) = &pool.instance_buffer else {
+            return Ok(());
+        };
+        let Some(instances) = instance_buffer.slice(range) else {
+            return Ok(());
+        };
+        let Ok(instances) = instances.per_instance() else {
+            return Ok(());
+        };
+
+        self.count_draw_call(None);
+        target
+            .draw(
+                (&instancing.quad, instances),
+                NoIndices(PrimitiveType::TriangleStrip),
+                &instancing.program,
+                &uniforms,
+                &parameters,
+            )
+            .map_err(OverlayError::GliumError)
+    }
+
+    /// Issues a single draw call with the program matching the batch's texture.
+    fn draw_batch<T: Surface>(
+        &self,
+        target: &mut T,
+        batch: Batch<'_>,
+        projection: math::Matrix4x4,
+    ) -> Result<(), OverlayError> {
+        if let Some(material) = batch.material {
+            let program = self
+                .materials
+                .get(&material.name)
+                .ok_or_else(|| OverlayError::MaterialNotFound(material.name.clone()))?;
+            let (width, height) = target.get_dimensions();
+            let uniforms = MaterialUniforms {
+                projection: projection.data,
+                resolution: [width as f32, height as f32],
+                texture: batch.texture,
+                textures: &self.textures,
+                material,
+            };
+            let blend = blending(batch.blend);
+            self.count_draw_call(batch.texture.map(Texture2D::id));
+            return self.submit(target, batch, program, &uniforms, blend);
+        }
+
+        let premultiplied = batch.blend == BlendMode::Premultiplied;
+        let Some(texture) = batch.texture else {
+            let uniforms = uniform! {
+                projection: projection.data,
+                premultiplied: premultiplied,
+            };
+            let blend = blending(batch.blend);
+            self.count_draw_call(None);
+            return self.submit(target, batch, &self.shape_program, &uniforms, blend);
+        };
+
+        let Some(gl_texture) = self.textures.get(texture) else {
+            return Ok(());
+        };
+        let tex = gl_texture
+            .sampled()
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Repeat);
+
+        // subpixel coverage needs its own blending, whatever the blend mode
+        let (program, blend) = match &self.subpixel_program {
+            Some(program) if batch.subpixel => (program, subpixel_blending()),
+            _ => (&self.texture_program, blending(batch.blend)),
+        };
+
+        let uniforms = uniform! {
+            projection: projection.data,
+            tex: tex,
+            premultiplied: premultiplied,
+            tex_premultiplied: texture.premultiplied,
+        };
+        self.count_draw_call(Some(texture.id()));
+        self.submit(target, batch, program, &uniforms, blend)
+    }
+
+    fn submit<T: Surface, U: Uniforms>(
+        &self,
+        target: &mut T,
+        batch: Batch<'_>,
+        program: &glium::Program,
+        uniforms: &U,
+        blend: glium::Blend,
+    ) -> Result<(), OverlayError> {
+        target
+            .draw(
+                batch.vertices,
+                batch.indices,
+                program,
+                uniforms,
+                &DrawParameters {
+                    blend,
+                    multisampling: true,
+                    scissor: batch.scissor,
+                    ..Default::default()
+                },
+            )
+            .map_err(OverlayError::GliumError)
+    }
+}
+
+/// Returns the blending for a blend mode. Shaders output colors premultiplied by their alpha.
+fn blending(mode: BlendMode) -> glium::Blend {
+    use glium::{BlendingFunction, LinearBlendingFactor};
+
+    let color = |source, destination| BlendingFunction::Addition {
+        source,
+        destination,
+    };
+    let over = color(
+        LinearBlendingFactor::One,
+        LinearBlendingFactor::OneMinusSourceAlpha,
+    );
+
+    let color = match mode {
+        BlendMode::Normal | BlendMode::Premultiplied => over,
+        BlendMode::Additive => color(LinearBlendingFactor::One, LinearBlendingFactor::One),
+        BlendMode::Multiply => color(
+            LinearBlendingFactor::DestinationColor,
+            LinearBlendingFactor::OneMinusSourceAlpha,
+        ),
+        BlendMode::Screen => color(
+            LinearBlendingFactor::One,
+            LinearBlendingFactor::OneMinusSourceColor,
+        ),
+    };
+
+    glium::Blend {
+        color,
+        // coverage accumulates the same way whatever the mode, so transparent windows stay see-through
+        alpha: over,
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    }
+}
+
+/// Blends each color channel by its own coverage, taken from the second fragment output.
+fn subpixel_blending() -> glium::Blend {
+    use glium::{BlendingFunction, LinearBlendingFactor};
+
+    glium::Blend {
+        color: BlendingFunction::Addition {
+            source: LinearBlendingFactor::SourceOneColor,
+            destination: LinearBlendingFactor::OneMinusSourceOneColor,
+        },
+        alpha: BlendingFunction::Addition {
+            source: LinearBlendingFactor::SourceOneAlpha,
+            destination: LinearBlendingFactor::OneMinusSourceOneAlpha,
+        },
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    }
+}
+
+/// Converts a clip rectangle in screen coordinates to a scissor rectangle, which starts at the bottom left.
+///
+/// Flipped, screen coordinates start at the bottom left too. The rectangle is cut to the
+/// target, and is empty if the clip rectangle lies outside of it.
+fn scissor_rect(
+    clip: Rect,
+    (target_width, target_height): (u32, u32),
+    flipped: bool,
+) -> glium::Rect {
+    let (width, height) = (target_width as f32, target_height as f32);
+    let left = clip[0][0].floor().clamp(0.0, width);
+    let top = clip[0][1].floor().clamp(0.0, height);
+    let right = clip[1][0].ceil().clamp(left, width);
+    let bottom = clip[1][1].ceil().clamp(top, height);
+
+    glium::Rect {
+        left: left as u32,
+        bottom: if flipped {
+            top as u32
+        } else {
+            (target_height as f32 - bottom) as u32
+        },
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scissor_rect;
+
+    #[test]
+    fn scissor_rect_inside_target() {
+        let rect = scissor_rect([[10.0, 20.0], [50.0, 40.0]], (100, 100), false);
+        assert_eq!(
+            rect,
+            glium::Rect {
+                left: 10,
+                bottom: 60,
+                width: 40,
+                height: 20
+            }
+        );
+        let rect = scissor_rect([[10.0, 20.0], [50.0, 40.0]], (100, 100), true);
+        assert_eq!(rect.bottom, 20);
+    }
+
+    #[test]
+    fn scissor_rect_outside_target() {
+        for flipped in [false, true] {
+            for clip in [
+                [[10.0, 150.0], [50.0, 180.0]],
+                [[150.0, 10.0], [180.0, 50.0]],
+                [[-80.0, -60.0], [-20.0, -10.0]],
+            ] {
+                let rect = scissor_rect(clip, (100, 100), flipped);
+                assert_eq!(rect.width * rect.height, 0, "{:?}", clip);
+            }
+        }
+    }
+
+    #[test]
+    fn scissor_rect_partly_outside_target() {
+        let rect = scissor_rect([[80.0, 90.0], [150.0, 130.0]], (100, 100), false);
+        assert_eq!(
+            rect,
+            glium::Rect {
+                left: 80,
+                bottom: 0,
+                width: 20,
+                height: 10
+            }
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{frame::Frame, software::SoftwareRenderer};
+
+/// Environment variable that makes `GoldenTest` write the rendered images as the new goldens
+/// instead of comparing against them.
+pub const BLESS_VARIABLE: &str = "OVERLAYLIB_BLESS";
+
+#[derive(Debug)]
+pub enum GoldenError {
+    /// There is no golden image yet. Run with `OVERLAYLIB_BLESS=1` to create it.
+    Missing(PathBuf),
+    /// The golden image has different dimensions than the rendered one.
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// Too many pixels differ from the golden image by more than the tolerance.
+    Mismatch {
+        mismatched: usize,
+        max_difference: u8,
+        /// Where the rendered image and the diff image were written.
+        actual: PathBuf,
+        diff: PathBuf,
+    },
+    Io(std::io::Error),
+    Image(image::ImageError),
+}
+
+impl std::fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GoldenError::Missing(path) => write!(
+                f,
+                "golden image {} is missing, run with {}=1 to create it",
+                path.display(),
+                BLESS_VARIABLE
+            ),
+            GoldenError::SizeMismatch { expected, actual } => write!(
+                f,
+                "golden image is {}x{} but the rendered image is {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            GoldenError::Mismatch {
+                mismatched,
+                max_difference,
+                actual,
+                diff,
+            } => write!(
+                f,
+                "{} pixels differ from the golden image, by up to {} (rendered image: {}, diff: {})",
+                mismatched,
+                max_difference,
+                actual.display(),
+                diff.display()
+            ),
+            GoldenError::Io(e) => write!(f, "failed to access golden image: {}", e),
+            GoldenError::Image(e) => write!(f, "failed to read or write golden image: {}", e),
+        }
+    }
+}
+
+/// How a rendered image differs from its golden image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Comparison {
+    /// Number of pixels where a channel differs by more than the tolerance.
+    pub mismatched: usize,
+    /// Largest difference of any channel of any pixel.
+    pub max_difference: u8,
+}
+
+/// Compares rendered frames with golden images stored as PNGs, to catch rendering regressions.
+///
+/// Frames are drawn by `software::SoftwareRenderer`, so the tests run without a GPU and don't
+/// depend on its driver. On failure, the rendered image and a diff image, with the
+/// mismatched pixels in red over the faded golden image, are written next to the golden one as
+/// `<name>.actual.png` and `<name>.diff.png`.
+///
+/// Set the `OVERLAYLIB_BLESS` environment variable to write the rendered images as the new
+/// goldens, after checking that the changes are intended.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let overlay = Overlay::new()?;
+/// let mut frame = overlay.new_frame();
+/// frame.add(Text::new("Hello").position([10.0, 10.0]));
+///
+/// GoldenTest::new("tests/golden")
+///     .tolerance(2)
+///     .check_frame("hello", &frame, 100, 40)
+///     .unwrap_or_else(|e| panic!("{}", e));
+/// ```
+#[derive(Debug, Clone)]
+pub struct GoldenTest {
+    directory: PathBuf,
+    /// Largest difference of a channel that doesn't count as a mismatch.
+    tolerance: u8,
+    /// Number of mismatched pixels allowed before the test fails.
+    max_mismatched: usize,
+}
+
+impl GoldenTest {
+    /// Compares against the golden images in `directory`, which have to match exactly.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            tolerance: 0,
+            max_mismatched: 0,
+        }
+    }
+
+    pub fn tolerance(self, tolerance: u8) -> Self {
+        Self { tolerance, ..self }
+    }
+
+    pub fn max_mismatched(self, max_mismatched: usize) -> Self {
+        Self {
+            max_mismatched,
+            ..self
+        }
+    }
+
+    /// Renders `frame` into a transparent `width` x `height` image and compares it with the golden
+    /// image called `name`.
+    pub fn check_frame(
+        &self,
+        name: &str,
+        frame: &Frame<'_>,
+        width: u32,
+        height: u32,
+    ) -> Result<(), GoldenError> {
+        let image = SoftwareRenderer::new().render_image(frame, width, height);
+        self.check(name, &image)
+    }
+
+    /// Compares `image` with the golden image called `name`.
+    pub fn check(&self, name: &str, image: &RgbaImage) -> Result<(), GoldenError> {
+        let path = self.path(name, "png");
+        let actual = self.path(name, "actual.png");
+        let diff = self.path(name, "diff.png");
+        // leftovers of a previous failure would be mistaken for this run's
+        for stale in [&actual, &diff] {
+            if stale.exists() {
+                std::fs::remove_file(stale).map_err(GoldenError::Io)?;
+            }
+        }
+
+        if std::env::var_os(BLESS_VARIABLE).is_some() {
+            std::fs::create_dir_all(&self.directory).map_err(GoldenError::Io)?;
+            return image.save(&path).map_err(GoldenError::Image);
+        }
+        if !path.exists() {
+            return Err(GoldenError::Missing(path));
+        }
+
+        let expected = image::open(&path).map_err(GoldenError::Image)?.to_rgba8();
+        if expected.dimensions() != image.dimensions() {
+            image.save(&actual).map_err(GoldenError::Image)?;
+            return Err(GoldenError::SizeMismatch {
+                expected: expected.dimensions(),
+                actual: image.dimensions(),
+            });
+        }
+
+        let comparison = compare(&expected, image, self.tolerance);
+        if comparison.mismatched <= self.max_mismatched {
+            return Ok(());
+        }
+
+        image.save(&actual).map_err(GoldenError::Image)?;
+        diff_image(&expected, image, self.tolerance)
+            .save(&diff)
+            .map_err(GoldenError::Image)?;
+        Err(GoldenError::Mismatch {
+            mismatched: comparison.mismatched,
+            max_difference: comparison.max_difference,
+            actual,
+            diff,
+        })
+    }
+
+    fn path(&self, name: &str, extension: &str) -> PathBuf {
+        self.directory.join(format!("{}.{}", name, extension))
+    }
+}
+
+/// Compares two images of the same size, pixel by pixel.
+///
+/// # Panics
+///
+/// Panics if the images have different dimensions.
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> Comparison {
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "compared images have different dimensions"
+    );
+    let mut comparison = Comparison::default();
+    for (expected, actual) in expected.pixels().zip(actual.pixels()) {
+        let difference = difference(expected, actual);
+        if difference > tolerance {
+            comparison.mismatched += 1;
+        }
+        comparison.max_difference = comparison.max_difference.max(difference);
+    }
+    comparison
+}
+
+/// Shows the pixels that differ by more than `tolerance` in red, over the faded expected image.
+pub fn diff_image(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> RgbaImage {
+    RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let expected = expected.get_pixel(x, y);
+        let difference = difference(expected, actual.get_pixel(x, y));
+        if difference > tolerance {
+            // even barely mismatched pixels stand out
+            return Rgba([128 + difference / 2, 0, 0, 255]);
+        }
+        // colors are premultiplied, so transparent areas fade to black
+        let luma = (expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 3;
+        let faded = (luma / 4) as u8;
+        Rgba([faded, faded, faded, 255])
+    })
+}
+
+/// Largest difference between the channels of two pixels.
+fn difference(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0)
+        .map(|(a, b)| a.abs_diff(b))
+        .max()
+        .unwrap_or(0)
+}
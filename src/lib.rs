@@ -1,17 +1,29 @@
 #![feature(anonymous_lifetime_in_impl_trait)]
 
-use std::{collections::HashMap, fmt::Formatter};
-
-use font::Font;
-use glium::{
-    backend::Facade, implement_vertex, program, uniform, DrawError, DrawParameters, Surface,
-};
+use std::{cell::RefCell, collections::HashMap, fmt::Formatter};
+
+use font::{Font, FontError, FontOptions};
+use capture::CaptureOptions;
+use glium::{backend::Facade, implement_vertex, DrawError, ReadError, Surface};
+use image::RgbaImage;
+use glium_renderer::GliumRenderer;
+use renderer::Renderer;
 use texture::Texture2D;
 
+mod atlas;
+pub mod capture;
+pub mod draw_list;
 pub mod font;
 pub mod frame;
+pub mod glium_renderer;
+pub mod golden;
+pub mod material;
 pub mod math;
 pub mod primitives;
+pub mod renderer;
+pub mod software;
+pub mod stats;
+pub mod system_font;
 pub mod texture;
 
 #[derive(Copy, Clone, Default)]
@@ -32,16 +44,18 @@ impl std::fmt::Debug for Vertex {
 
 pub type Point = [f32; 2];
 pub type Color = [f32; 4];
+/// An axis-aligned rectangle, as `[top_left, bottom_right]`.
 pub type Rect = [Point; 2];
 
 implement_vertex!(Vertex, position, tex_coords, color);
 
+/// Holds the fonts and textures frames are built with, and draws them with glium.
 pub struct Overlay {
-    texture_program: glium::Program,
-    shape_program: glium::Program,
     pub fonts: HashMap<usize, Font>,
     pub textures: Vec<Texture2D>,
     font_stack: Vec<usize>,
+    /// None for overlays created with `Overlay::new`, which only build frames.
+    renderer: Option<RefCell<GliumRenderer>>,
 }
 
 #[derive(Debug)]
@@ -50,129 +64,133 @@ pub enum OverlayError {
     TextureCreationError,
     ShaderCompilationError,
     FileNotFound,
+    FontError(FontError),
+    /// No installed font matches the requested family name.
+    FontNotFound(String),
+    /// A frame uses a material that wasn't added to the overlay.
+    MaterialNotFound(String),
+    /// The overlay was created with `Overlay::new`, which has no renderer to draw or compile shaders with.
+    NoRenderer,
+    /// Frames can only be drawn into textures created with `Texture2D::render_target`.
+    NotRenderTarget,
     GliumError(DrawError),
+    /// Reading the window back failed, see `Overlay::capture`.
+    ReadError(ReadError),
 }
 
-impl Overlay {
-    pub fn initialize<F>(facade: &F) -> Result<Self, OverlayError>
-    where
-        F: ?Sized + Facade,
-    {
-        let texture_program = program!(facade,
-            140 => {
-                vertex: "
-                #version 140
-
-                in vec2 position;
-                in vec2 tex_coords;
-                in vec4 color;
-
-                out vec4 v_color;
-                out vec2 v_tex_coords;
-
-                uniform mat4 projection;
-
-                void main() {
-                    gl_Position = projection * vec4(position, 0.0, 1.0);
-                    v_color = color;
-                    v_tex_coords = tex_coords;
-                }
-                ",
-                fragment: "
-                #version 140
-
-                in vec4 v_color;
-                in vec2 v_tex_coords;
-
-                out vec4 color;
-                uniform sampler2D tex;
-
-                void main() {
-                    color = texture(tex, v_tex_coords).rgba * v_color;
-                }
-                "
-            },
-        )
-        .map_err(|_| OverlayError::ShaderCompilationError)?;
-
-        let shape_program = program!(facade,
-            140 => {
-                vertex: "
-                #version 140
-
-                in vec2 position;
-                in vec2 tex_coords;
-                in vec4 color;
-
-                out vec4 v_color;
-                out vec2 v_tex_coords;
-
-                uniform mat4 projection;
-
-                void main() {
-                    gl_Position = projection * vec4(position, 0.0, 1.0);
-                    v_color = color;
-                    v_tex_coords = tex_coords;
-                }
-                ",
-                fragment: "
-                #version 140
-
-                in vec4 v_color;
-                in vec2 v_tex_coords;
-
-                out vec4 color;
-
-                void main() {
-                    color = v_color;
-                }
-                "
-            },
-        )
-        .map_err(|_| OverlayError::ShaderCompilationError)?;
 
+impl Overlay {
+    /// Creates an overlay without a renderer, with the default font loaded.
+    ///
+    /// Its frames can be drawn by any `renderer::Renderer`, like `software::SoftwareRenderer`,
+    /// without an OpenGL context.
+    pub fn new() -> Result<Self, OverlayError> {
         let font = Font::new(
-            facade,
             include_bytes!("../assets/fonts/NotoSansMono-Regular.ttf"),
             24.0,
-        );
+        )
+        .map_err(OverlayError::FontError)?;
 
         let mut fonts = HashMap::new();
         fonts.insert(0, font);
 
         Ok(Self {
-            texture_program,
-            shape_program,
             fonts,
             font_stack: vec![0],
             textures: vec![],
+            renderer: None,
         })
     }
 
+    /// Creates an overlay drawn with glium, with the default font loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `facade` - The glium facade.
+    pub fn initialize<F>(facade: &F) -> Result<Self, OverlayError>
+    where
+        F: ?Sized + Facade,
+    {
+        Ok(Self {
+            renderer: Some(RefCell::new(GliumRenderer::new(facade)?)),
+            ..Self::new()?
+        })
+    }
+
+    /// Returns whether the GPU draws `primitives::ShapeInstance`s with hardware instancing.
+    ///
+    /// Always false without a renderer.
+    pub fn supports_instancing(&self) -> bool {
+        self.renderer
+            .as_ref()
+            .is_some_and(|renderer| renderer.borrow().supports_instancing())
+    }
+
     /// Adds a font from a file to the overlay.
     ///
     /// The font will be added to the end of the font list.
     ///
     /// # Arguments
     ///
-    /// * `facade` - The glium facade.
     /// * `path` - The path to the font file.
     /// * `size` - The font size.
     /// * `id` - The font ID. Used to reference the font later.
-    pub fn add_font_from_file<F>(
+    pub fn add_font_from_file(
         &mut self,
-        facade: &F,
         path: &str,
         size: f32,
         id: usize,
     ) -> Result<(), OverlayError>
-    where
-        F: ?Sized + Facade,
+    {
+        self.add_font_from_file_with_options(path, size, id, FontOptions::default())
+    }
+
+    /// Adds a font from a file to the overlay, rasterized with the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the font file.
+    /// * `size` - The font size.
+    /// * `id` - The font ID. Used to reference the font later.
+    /// * `options` - Hinting, subpixel rendering and gamma settings.
+    pub fn add_font_from_file_with_options(
+        &mut self,
+        path: &str,
+        size: f32,
+        id: usize,
+        options: FontOptions,
+    ) -> Result<(), OverlayError>
     {
         let font_data = std::fs::read(path).map_err(|_| OverlayError::FileNotFound)?;
-        let font = Font::new(facade, &font_data, size);
-        self.fonts.insert(id, font);
-        Ok(())
+        self.add_font_from_memory_with_options(&font_data, size, id, options)
+    }
+
+    /// Adds an installed font to the overlay, looked up by its family name.
+    ///
+    /// The standard font directories and the fontconfig configuration are scanned for the family,
+    /// and the upright face whose weight is closest to `weight` is picked. Scanning reads every
+    /// installed font, so this is best done once at startup.
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - The family name, e.g. "DejaVu Sans". Matched case-insensitively.
+    /// * `weight` - The desired weight, from 100 to 900. See `system_font::weight`.
+    /// * `size` - The font size.
+    /// * `id` - The font ID. Used to reference the font later.
+    pub fn add_system_font(
+        &mut self,
+        family: &str,
+        weight: u16,
+        size: f32,
+        id: usize,
+    ) -> Result<(), OverlayError>
+    {
+        let font = system_font::find(family, weight)
+            .ok_or_else(|| OverlayError::FontNotFound(family.to_string()))?;
+        // read by its path, which may not be UTF-8, unlike the ones `add_font_from_file` takes
+        let font_data = std::fs::read(&font.path).map_err(|_| OverlayError::FileNotFound)?;
+        let options = FontOptions::default().face_index(font.face_index);
+        self.add_font_from_memory_with_options(&font_data, size, id, options)
     }
 
     /// Adds a font from memory to the overlay.
@@ -181,16 +199,81 @@ impl Overlay {
     ///
     /// # Arguments
     ///
-    /// * `facade` - The glium facade.
     /// * `data` - The font data.
     /// * `size` - The font size.
     /// * `id` - The font ID. Used to reference the font later.
-    pub fn add_font_from_memory<F>(&mut self, facade: &F, data: &[u8], size: f32, id: usize)
-    where
-        F: ?Sized + Facade,
+    pub fn add_font_from_memory(
+        &mut self,
+        data: &[u8],
+        size: f32,
+        id: usize,
+    ) -> Result<(), OverlayError>
     {
-        let font = Font::new(facade, data, size);
+        self.add_font_from_memory_with_options(data, size, id, FontOptions::default())
+    }
+
+    /// Adds a font from memory to the overlay, rasterized with the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The font data.
+    /// * `size` - The font size.
+    /// * `id` - The font ID. Used to reference the font later.
+    /// * `options` - Hinting, subpixel rendering and gamma settings.
+    pub fn add_font_from_memory_with_options(
+        &mut self,
+        data: &[u8],
+        size: f32,
+        id: usize,
+        options: FontOptions,
+    ) -> Result<(), OverlayError>
+    {
+        let font = Font::with_options(data, size, options).map_err(OverlayError::FontError)?;
         self.fonts.insert(id, font);
+        Ok(())
+    }
+
+    /// Adds a material, drawn with the built-in vertex shader and the given fragment shader.
+    ///
+    /// See `GliumRenderer::add_material` for what the shader gets.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The material name. Used to reference the material in `material::Material`.
+    /// * `fragment_shader` - GLSL source of the fragment shader, version 140 or later.
+    pub fn add_material(&mut self, name: &str, fragment_shader: &str) -> Result<(), OverlayError> {
+        self.renderer_mut()?.add_material(name, fragment_shader)
+    }
+
+    /// Adds a material with its own vertex shader.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The material name. Used to reference the material in `material::Material`.
+    /// * `vertex_shader` - GLSL source of the vertex shader, version 140 or later.
+    /// * `fragment_shader` - GLSL source of the fragment shader.
+    pub fn add_material_with_vertex_shader(
+        &mut self,
+        name: &str,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<(), OverlayError> {
+        self.renderer_mut()?
+            .add_material_with_vertex_shader(name, vertex_shader, fragment_shader)
+    }
+
+    /// Returns whether a material called `name` was added.
+    pub fn has_material(&self, name: &str) -> bool {
+        self.renderer
+            .as_ref()
+            .is_some_and(|renderer| renderer.borrow().has_material(name))
+    }
+
+    fn renderer_mut(&mut self) -> Result<&mut GliumRenderer, OverlayError> {
+        self.renderer
+            .as_mut()
+            .map(RefCell::get_mut)
+            .ok_or(OverlayError::NoRenderer)
     }
 
     /// Creates a new frame.
@@ -222,89 +305,163 @@ impl Overlay {
         self.fonts.get(self.font_stack.last()?)
     }
 
+    /// Removes every texture from the texture atlas.
+    ///
+    /// Textures are copied into the atlas the first time they are drawn, and their space is
+    /// reused once they are dropped. Rows of the atlas keep the height of the first texture
+    /// packed into them though, so call this to repack it when the textures drawn change a lot.
+    pub fn clear_atlas(&self) {
+        if let Some(renderer) = &self.renderer {
+            renderer.borrow_mut().clear_atlas();
+        }
+    }
+
+    /// Returns the statistics of the last frame drawn, with `draw`, `draw_to_texture` or
+    /// `capture_frame`.
+    ///
+    /// Draw them with `primitives::StatsPanel` to see what a frame costs. Always empty without a renderer.
+    pub fn stats(&self) -> stats::FrameStats {
+        self.renderer
+            .as_ref()
+            .map(|renderer| renderer.borrow().stats())
+            .unwrap_or_default()
+    }
+
     /// Draws the overlay.
     ///
+    /// Shapes, text and small textures are copied into a shared texture atlas and drawn together,
+    /// so consecutive commands only need separate draw calls when their clip rectangles differ.
+    ///
     /// # Arguments
     ///
-    /// * `facade` - The glium facade.
     /// * `target` - The glium frame.
-    /// * `draw_data` - The draw data.
+    /// * `draw_data` - The frame to draw. It is cleared afterwards.
     ///
     /// # Returns
     ///
-    /// * `Result<(), DrawError>` - The result of the draw operation.
+    /// * `Result<(), OverlayError>` - The result of the draw operation. What drawing took is
+    ///   recorded, see `stats`.
     ///
     /// # Example
     ///
-    /// ```rust
-    /// # use overlaylib::Overlay;
-    /// # use glium::Display;
-    /// # let display = Display::new();
-    /// # let overlay = Overlay::initialize(&display);
-    /// # let mut target = display.draw();
-    /// # let draw_data = vec![];
-    /// overlay.draw(&display, &mut target, draw_data);
+    /// ```rust,ignore
+    /// let overlay = Overlay::initialize(&display)?;
+    /// let mut frame = overlay.new_frame();
+    /// frame.add(Rectangle::new().dimensions([100.0, 100.0]));
+    ///
+    /// let mut target = display.draw();
+    /// overlay.draw(&mut target, &mut frame)?;
+    /// target.finish()?;
     /// ```
-    pub fn draw<F: Facade, T: Surface>(
+    pub fn draw<T: Surface>(
         &self,
-        facade: &F,
         target: &mut T,
         draw_data: &mut crate::frame::Frame<'_>,
     ) -> Result<(), OverlayError> {
-        let (width, height) = target.get_dimensions();
-        let projection =
-            math::Matrix4x4::orthographic(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
-        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-        for buffer in &mut draw_data.buffers {
-            match buffer.texture {
-                Some(texture) => {
-                    let vertex_buffer = glium::VertexBuffer::new(facade, &buffer.vertices)
-                        .map_err(|_| OverlayError::BufferCreationError)?;
-
-                    let tex = texture
-                        .get_gl_texture()
-                        .sampled()
-                        .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
-                        .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
-                        .wrap_function(glium::uniforms::SamplerWrapFunction::Repeat);
-
-                    target.draw(
-                        &vertex_buffer,
-                        &indices,
-                        &self.texture_program,
-                        &uniform! {
-                            projection: projection.data,
-                            tex: tex,
-                        },
-                        &DrawParameters {
-                            blend: glium::Blend::alpha_blending(),
-                            multisampling: true,
-                            ..Default::default()
-                        },
-                    ).map_err(|e| OverlayError::GliumError(e))?;
-                }
-                None => {
-                    let vertex_buffer = glium::VertexBuffer::new(facade, &buffer.vertices)
-                        .map_err(|_| OverlayError::BufferCreationError)?;
-                    let indices =
-                        glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-                    target.draw(
-                        &vertex_buffer,
-                        &indices,
-                        &self.shape_program,
-                        &uniform! { projection: projection.data },
-                        &DrawParameters {
-                            blend: glium::Blend::alpha_blending(),
-                            multisampling: true,
-                            ..Default::default()
-                        },
-                    ).map_err(|e| OverlayError::GliumError(e))?;
-                }
-            }
-        }
+        let renderer = self.renderer.as_ref().ok_or(OverlayError::NoRenderer)?;
+        renderer.borrow_mut().render(target, draw_data)?;
+
+        draw_data.clear();
+
+        Ok(())
+    }
+
+    /// Draws a frame into a texture instead of the screen, replacing the texture's contents.
+    ///
+    /// The texture has to be created with `Texture2D::render_target`, and can then be drawn by
+    /// the frames of this overlay like any other: render a complex widget like a minimap once and
+    /// draw it many times, or draw it with a material for post-processing.
+    ///
+    /// # Arguments
+    ///
+    /// * `texture` - The render target.
+    /// * `draw_data` - The frame to draw. It is cleared afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let minimap = Texture2D::render_target((256, 256));
+    /// let mut frame = overlay.new_frame();
+    /// frame.add(Circle::new().position([128.0, 128.0]).radius(100.0));
+    /// overlay.draw_to_texture(&minimap, &mut frame)?;
+    ///
+    /// let mut frame = overlay.new_frame();
+    /// frame.add(Rectangle::new().dimensions([256.0, 256.0]).texture(&minimap));
+    /// overlay.draw(&mut target, &mut frame)?;
+    /// ```
+    pub fn draw_to_texture(
+        &self,
+        texture: &Texture2D,
+        draw_data: &mut crate::frame::Frame<'_>,
+    ) -> Result<(), OverlayError> {
+        let renderer = self.renderer.as_ref().ok_or(OverlayError::NoRenderer)?;
+        renderer.borrow_mut().render_to_texture(texture, draw_data)?;
 
         draw_data.clear();
 
         Ok(())
     }
+
+    /// Takes a screenshot of the window, reading back what was last presented in it.
+    ///
+    /// Call it after `draw` and swapping buffers. The image has straight alpha and can be saved
+    /// as PNG or WebP with `RgbaImage::save`. To capture the overlay without what the
+    /// application drew under it in the same window, use `capture_frame`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Whether to keep the alpha channel.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// if screenshot_pressed {
+    ///     let image = overlay.capture(CaptureOptions::new().alpha(true))?;
+    ///     image.save("screenshot.png")?;
+    /// }
+    /// ```
+    pub fn capture(&self, options: CaptureOptions) -> Result<RgbaImage, OverlayError> {
+        let renderer = self.renderer.as_ref().ok_or(OverlayError::NoRenderer)?;
+        let mut image = renderer.borrow().read_front_buffer()?;
+        if options.alpha {
+            capture::unpremultiply(&mut image);
+        } else {
+            capture::make_opaque(&mut image);
+        }
+        Ok(image)
+    }
+
+    /// Draws a frame alone into an offscreen image, with its alpha preserved.
+    ///
+    /// The frame isn't cleared, so it can still be drawn on screen afterwards. Overlays created
+    /// with `Overlay::new` draw it with `software::SoftwareRenderer`. The image has straight
+    /// alpha and can be saved as PNG or WebP with `RgbaImage::save`.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw_data` - The frame to capture.
+    /// * `dimensions` - The size of the image, usually the size of the window.
+    pub fn capture_frame(
+        &self,
+        draw_data: &crate::frame::Frame<'_>,
+        dimensions: (u32, u32),
+    ) -> Result<RgbaImage, OverlayError> {
+        let mut image = match &self.renderer {
+            Some(renderer) => {
+                let mut renderer = renderer.borrow_mut();
+                let target = Texture2D::render_target(dimensions);
+                renderer.render_to_texture(&target, draw_data)?;
+                renderer
+                    .read_texture(&target)
+                    .ok_or(OverlayError::TextureCreationError)?
+            }
+            None => software::SoftwareRenderer::new().render_image(
+                draw_data,
+                dimensions.0,
+                dimensions.1,
+            ),
+        };
+        capture::unpremultiply(&mut image);
+        Ok(image)
+    }
 }
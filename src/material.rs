@@ -0,0 +1,191 @@
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue,
+    Uniforms,
+};
+
+use crate::{glium_renderer::GpuTextures, texture::Texture2D};
+
+/// Vertex shader used by materials registered with only a fragment shader.
+///
+/// It passes these to the fragment shader:
+///
+/// * `v_color` - The vertex color, with the frame's tint and alpha applied.
+/// * `v_tex_coords` - The texture coordinates.
+/// * `v_position` - The position in pixels, from the top left of the target.
+pub const DEFAULT_VERTEX_SHADER: &str = "
+#version 140
+
+in vec2 position;
+in vec2 tex_coords;
+in vec4 color;
+
+out vec4 v_color;
+out vec2 v_tex_coords;
+out vec2 v_position;
+
+uniform mat4 projection;
+
+void main() {
+    gl_Position = projection * vec4(position, 0.0, 1.0);
+    v_color = color;
+    v_tex_coords = tex_coords;
+    v_position = position;
+}
+";
+
+/// A value for a uniform declared by a material's shaders.
+#[derive(PartialEq)]
+pub enum MaterialValue {
+    Float(f32),
+    Int(i32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([[f32; 4]; 4]),
+    /// Sampled with linear filtering and repeated outside of 0 to 1. Holds on to the texture,
+    /// so materials can outlive the textures they were given.
+    Texture(Texture2D),
+}
+
+impl Clone for MaterialValue {
+    fn clone(&self) -> Self {
+        match self {
+            MaterialValue::Float(value) => MaterialValue::Float(*value),
+            MaterialValue::Int(value) => MaterialValue::Int(*value),
+            MaterialValue::Vec2(value) => MaterialValue::Vec2(*value),
+            MaterialValue::Vec3(value) => MaterialValue::Vec3(*value),
+            MaterialValue::Vec4(value) => MaterialValue::Vec4(*value),
+            MaterialValue::Mat4(value) => MaterialValue::Mat4(*value),
+            MaterialValue::Texture(texture) => MaterialValue::Texture(texture.share()),
+        }
+    }
+}
+
+impl From<f32> for MaterialValue {
+    fn from(value: f32) -> Self {
+        MaterialValue::Float(value)
+    }
+}
+
+impl From<i32> for MaterialValue {
+    fn from(value: i32) -> Self {
+        MaterialValue::Int(value)
+    }
+}
+
+impl From<[f32; 2]> for MaterialValue {
+    fn from(value: [f32; 2]) -> Self {
+        MaterialValue::Vec2(value)
+    }
+}
+
+impl From<[f32; 3]> for MaterialValue {
+    fn from(value: [f32; 3]) -> Self {
+        MaterialValue::Vec3(value)
+    }
+}
+
+impl From<[f32; 4]> for MaterialValue {
+    fn from(value: [f32; 4]) -> Self {
+        MaterialValue::Vec4(value)
+    }
+}
+
+impl From<[[f32; 4]; 4]> for MaterialValue {
+    fn from(value: [[f32; 4]; 4]) -> Self {
+        MaterialValue::Mat4(value)
+    }
+}
+
+impl From<&Texture2D> for MaterialValue {
+    fn from(value: &Texture2D) -> Self {
+        MaterialValue::Texture(value.share())
+    }
+}
+
+/// A registered material along with the values of its uniforms, as used by the primitives of a frame.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// frame.push_material(Material::new("scanlines").uniform("time", elapsed));
+/// frame.add(Rectangle::new().dimensions([300.0, 200.0]));
+/// frame.pop_material();
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub uniforms: Vec<(String, MaterialValue)>,
+}
+
+impl Material {
+    /// Uses the material registered as `name`, with no uniforms set.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            uniforms: vec![],
+        }
+    }
+
+    /// Sets the uniform called `name`, replacing its previous value.
+    pub fn uniform(mut self, name: impl Into<String>, value: impl Into<MaterialValue>) -> Self {
+        let name = name.into();
+        let value = value.into();
+        match self.uniforms.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.uniforms.push((name, value)),
+        }
+        self
+    }
+}
+
+/// The uniforms of a material draw call: the built-in ones, then the material's own.
+pub(crate) struct MaterialUniforms<'m, 'a> {
+    pub projection: [[f32; 4]; 4],
+    pub resolution: [f32; 2],
+    pub texture: Option<&'a Texture2D>,
+    /// GPU copies of the textures, uploaded before drawing.
+    pub textures: &'m GpuTextures,
+    pub material: &'m Material,
+}
+
+impl Uniforms for MaterialUniforms<'_, '_> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        output("projection", UniformValue::Mat4(self.projection));
+        output("resolution", UniformValue::Vec2(self.resolution));
+        if let Some(texture) = self.texture {
+            if let Some(gl_texture) = self.textures.get(texture) {
+                output("tex", UniformValue::Texture2d(gl_texture, Some(sampler())));
+            }
+            output("tex_premultiplied", UniformValue::Bool(texture.premultiplied));
+        }
+        for (name, value) in &self.material.uniforms {
+            let value = match value {
+                MaterialValue::Float(value) => UniformValue::Float(*value),
+                MaterialValue::Int(value) => UniformValue::SignedInt(*value),
+                MaterialValue::Vec2(value) => UniformValue::Vec2(*value),
+                MaterialValue::Vec3(value) => UniformValue::Vec3(*value),
+                MaterialValue::Vec4(value) => UniformValue::Vec4(*value),
+                MaterialValue::Mat4(value) => UniformValue::Mat4(*value),
+                MaterialValue::Texture(texture) => match self.textures.get(texture) {
+                    Some(gl_texture) => UniformValue::Texture2d(gl_texture, Some(sampler())),
+                    None => continue,
+                },
+            };
+            output(name, value);
+        }
+    }
+}
+
+fn sampler() -> SamplerBehavior {
+    SamplerBehavior {
+        minify_filter: MinifySamplerFilter::Linear,
+        magnify_filter: MagnifySamplerFilter::Linear,
+        wrap_function: (
+            SamplerWrapFunction::Repeat,
+            SamplerWrapFunction::Repeat,
+            SamplerWrapFunction::Repeat,
+        ),
+        ..Default::default()
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(unused)]
 
-use crate::{Point};
+use crate::{Point, Rect, Vertex};
 
 pub fn magnitude(point: Point) -> f32 {
     (point[0].powi(2) + point[1].powi(2)).sqrt()
@@ -11,6 +11,126 @@ pub fn normalize(point: Point) -> Point {
     [point[0] / magnitude, point[1] / magnitude]
 }
 
+/// Returns the overlapping area of two rectangles. The result is empty (zero or negative size) if they don't overlap.
+pub fn rect_intersection(a: Rect, b: Rect) -> Rect {
+    [
+        [a[0][0].max(b[0][0]), a[0][1].max(b[0][1])],
+        [a[1][0].min(b[1][0]), a[1][1].min(b[1][1])],
+    ]
+}
+
+pub fn rect_is_empty(rect: Rect) -> bool {
+    rect[1][0] <= rect[0][0] || rect[1][1] <= rect[0][1]
+}
+
+/// Returns the smallest rectangle containing every vertex, or None if there are no vertices.
+pub fn bounding_rect(vertices: &[Vertex]) -> Option<Rect> {
+    let first = vertices.first()?.position;
+    let mut rect = [first, first];
+    for vertex in vertices {
+        rect[0][0] = rect[0][0].min(vertex.position[0]);
+        rect[0][1] = rect[0][1].min(vertex.position[1]);
+        rect[1][0] = rect[1][0].max(vertex.position[0]);
+        rect[1][1] = rect[1][1].max(vertex.position[1]);
+    }
+    Some(rect)
+}
+
+/// Replaces the outline of an indexed triangle mesh with a fringe `width` wide, fading from the
+/// edge's color to transparent. This anti-aliases the edges without multisampling.
+///
+/// The fringe is centered on the outline, so shapes keep their apparent size.
+///
+/// Outline edges are those used by a single triangle, so shapes need to share the vertices of their
+/// inner edges, as a fan or a quad do.
+pub fn feather_edges(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, width: f32) {
+    // every edge as (lower index, higher index, the triangle's third index, the triangle)
+    let mut edges: Vec<(u32, u32, u32, usize)> = indices
+        .chunks_exact(3)
+        .enumerate()
+        .flat_map(|(triangle, corners)| {
+            let [a, b, c] = [corners[0], corners[1], corners[2]];
+            [(a, b, c), (b, c, a), (c, a, b)].map(|(a, b, third)| (a, b, third, triangle))
+        })
+        .map(|(a, b, third, triangle)| (a.min(b), a.max(b), third, triangle))
+        .collect();
+    edges.sort_unstable_by_key(|&(a, b, ..)| (a, b));
+
+    let mut outline = Vec::with_capacity(edges.len());
+    let mut i = 0;
+    while i < edges.len() {
+        let shared = edges[i + 1..]
+            .iter()
+            .take_while(|edge| edges[i].0 == edge.0 && edges[i].1 == edge.1)
+            .count();
+        if shared == 0 {
+            outline.push(edges[i]);
+        }
+        i += shared + 1;
+    }
+
+    // outward normal of every outline edge, summed at its vertices with how many were summed
+    let mut normals: Vec<Option<(Point, f32)>> = vec![None; vertices.len()];
+    for &(a, b, third, _) in &outline {
+        let [pa, pb, pc] = [a, b, third].map(|index| vertices[index as usize].position);
+        let direction = [pb[0] - pa[0], pb[1] - pa[1]];
+        if magnitude(direction) == 0.0 {
+            continue;
+        }
+        let mut normal = normalize([direction[1], -direction[0]]);
+        // point away from the triangle the edge belongs to
+        if normal[0] * (pc[0] - pa[0]) + normal[1] * (pc[1] - pa[1]) > 0.0 {
+            normal = [-normal[0], -normal[1]];
+        }
+        for index in [a, b] {
+            let (sum, count) = normals[index as usize].get_or_insert(([0.0, 0.0], 0.0));
+            sum[0] += normal[0];
+            sum[1] += normal[1];
+            *count += 1.0;
+        }
+    }
+
+    let mut fringe = vec![0; vertices.len()];
+    for (index, normal) in normals.iter().enumerate() {
+        let Some((sum, count)) = *normal else {
+            continue;
+        };
+        // the average of two unit normals, divided by its squared length, is as far from both
+        // edges as a unit normal. It's limited so that sharp corners don't spike.
+        let average = [sum[0] / count, sum[1] / count];
+        let squared = (average[0] * average[0] + average[1] * average[1]).max(0.25);
+        let offset = [
+            average[0] / squared * width / 2.0,
+            average[1] / squared * width / 2.0,
+        ];
+        let vertex = vertices[index];
+        vertices[index].position = [
+            vertex.position[0] - offset[0],
+            vertex.position[1] - offset[1],
+        ];
+        fringe[index] = vertices.len() as u32;
+        vertices.push(Vertex {
+            position: [vertex.position[0] + offset[0], vertex.position[1] + offset[1]],
+            color: [vertex.color[0], vertex.color[1], vertex.color[2], 0.0],
+            ..vertex
+        });
+    }
+
+    // each fringe is drawn right after its triangle, so that later parts of the shape, like a
+    // border over its fill, cover it
+    outline.sort_unstable_by_key(|&(.., triangle)| triangle);
+    let mut outline = outline.into_iter().peekable();
+    let mut feathered = Vec::with_capacity(indices.len() + outline.len() * 6);
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        feathered.extend_from_slice(corners);
+        while let Some((a, b, ..)) = outline.next_if(|edge| edge.3 == triangle) {
+            let (outer_a, outer_b) = (fringe[a as usize], fringe[b as usize]);
+            feathered.extend_from_slice(&[a, b, outer_b, a, outer_b, outer_a]);
+        }
+    }
+    *indices = feathered;
+}
+
 
 use std::{
     f32::consts::PI,
@@ -101,6 +221,12 @@ impl From<[[f32; 4]; 4]> for Matrix4x4 {
     }
 }
 
+impl Default for Matrix4x4 {
+    fn default() -> Self {
+        Matrix4x4::identity()
+    }
+}
+
 impl Matrix4x4 {
     pub fn new() -> Matrix4x4 {
         Matrix4x4 {
@@ -154,6 +280,17 @@ impl Matrix4x4 {
         Matrix4x4::new()
     }
 
+    /// Transforms a 2D point, treating it as lying on the z = 0 plane.
+    ///
+    /// Like in GLSL, `data[i]` is the i-th column, so the translation lives in `data[3]`.
+    pub fn transform_point(&self, point: Point) -> Point {
+        let [x, y] = point;
+        [
+            self.data[0][0] * x + self.data[1][0] * y + self.data[3][0],
+            self.data[0][1] * x + self.data[1][1] * y + self.data[3][1],
+        ]
+    }
+
     pub fn orthographic(
         left: f32,
         right: f32,
@@ -1,6 +1,6 @@
 use crate::Vertex;
 
-use super::{Outline, Primitive, PrimitiveType, DEFAULT_COLOR};
+use super::{Geometry, Instanced, Outline, Primitive, ShapeInstance, DEFAULT_COLOR};
 
 pub struct Circle {
     pub position: [f32; 2],
@@ -27,49 +27,79 @@ impl Default for Circle {
     }
 }
 
-impl Primitive for Circle {
-    fn get_type(&self) -> PrimitiveType {
-        PrimitiveType::Circle
-    }
-
-    fn get_vertices(&self) -> Vec<Vertex> {
-        let mut buf = Vec::new();
+impl Primitive<'_> for Circle {
+    fn get_geometry(&self) -> Geometry {
+        let mut geometry = Geometry::new();
 
-        for i in 0..self.detail {
+        let point = |i: u32, radius: f32| {
             let angle = 2.0 * std::f32::consts::PI * (i as f32 / self.detail as f32);
-            let x = self.position[0] + self.radius * angle.cos();
-            let y = self.position[1] + self.radius * angle.sin();
-            let next_angle = 2.0 * std::f32::consts::PI * ((i + 1) as f32 / self.detail as f32);
-            let next_x = self.position[0] + self.radius * next_angle.cos();
-            let next_y = self.position[1] + self.radius * next_angle.sin();
-            if self.filled {
-                buf.push(Vertex {
-                    position: [x, y],
-                    color: self.color,
-                    tex_coords: [0.0, 0.0],
-                });
-                buf.push(Vertex {
-                    position: [next_x, next_y],
-                    color: self.color,
-                    tex_coords: [0.0, 0.0],
-                });
-                buf.push(Vertex {
-                    position: self.position,
+            [
+                self.position[0] + radius * angle.cos(),
+                self.position[1] + radius * angle.sin(),
+            ]
+        };
+
+        if self.filled {
+            // a fan around a single center vertex
+            geometry.vertices.push(Vertex {
+                position: self.position,
+                color: self.color,
+                tex_coords: [0.0, 0.0],
+            });
+            for i in 0..self.detail {
+                geometry.vertices.push(Vertex {
+                    position: point(i, self.radius),
                     color: self.color,
                     tex_coords: [0.0, 0.0],
                 });
+                let next = (i + 1) % self.detail;
+                geometry.indices.extend_from_slice(&[0, i + 1, next + 1]);
             }
-            if let Some(border) = self.border {
-                buf.extend(super::line::get_line(
-                    [x, y],
-                    [next_x, next_y],
-                    border.color,
-                    border.thickness,
-                ));
+        }
+
+        if let Some(border) = self.border {
+            // a ring of quads sharing their sides, so the outline is two closed loops. Like a
+            // `Line`, it reaches `thickness` to either side of the radius
+            let base = geometry.vertices.len() as u32;
+            let t = border.thickness;
+            for i in 0..self.detail {
+                for radius in [self.radius - t, self.radius + t] {
+                    geometry.vertices.push(Vertex {
+                        position: point(i, radius),
+                        color: border.color,
+                        tex_coords: [0.0, 0.0],
+                    });
+                }
+                let inner = base + i * 2;
+                let next = base + (i + 1) % self.detail * 2;
+                geometry
+                    .indices
+                    .extend_from_slice(&[inner, inner + 1, next + 1, inner, next + 1, next]);
             }
         }
 
-        buf
+        geometry
+    }
+}
+
+impl Instanced for Circle {
+    fn get_instance(&self) -> Option<ShapeInstance> {
+        // like the ring built by `get_geometry`, the border reaches `thickness` to either side
+        // of the radius, and instance borders are drawn inside the shape's edge
+        let (border_color, thickness) = self
+            .border
+            .map_or(([0.0; 4], 0.0), |border| (border.color, border.thickness));
+        let radius = self.radius + thickness;
+        let color = if self.filled { self.color } else { [0.0; 4] };
+
+        Some(ShapeInstance {
+            origin: [self.position[0] - radius, self.position[1] - radius],
+            size: [radius * 2.0, radius * 2.0],
+            color,
+            border_color,
+            radius,
+            border: thickness * 2.0,
+        })
     }
 }
 
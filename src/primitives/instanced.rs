@@ -0,0 +1,27 @@
+use glium::implement_vertex;
+
+/// A rounded rectangle drawn by the GPU from a single instance, without any vertices built on the CPU.
+///
+/// The shape is generated in the vertex shader and its edges are anti-aliased with a signed
+/// distance field. Circles are rounded rectangles whose radius is half their size.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ShapeInstance {
+    /// Top left corner.
+    pub origin: [f32; 2],
+    pub size: [f32; 2],
+    /// Fill color. Fully transparent for outlines.
+    pub color: [f32; 4],
+    pub border_color: [f32; 4],
+    /// Corner radius.
+    pub radius: f32,
+    /// Border thickness, drawn inside the shape's edge.
+    pub border: f32,
+}
+
+implement_vertex!(ShapeInstance, origin, size, color, border_color, radius, border);
+
+/// A primitive that can be drawn as a `ShapeInstance`.
+pub trait Instanced {
+    /// Returns the instance drawing this primitive, or None if it can only be drawn from its geometry.
+    fn get_instance(&self) -> Option<ShapeInstance>;
+}
@@ -1,6 +1,6 @@
 use crate::{math, Vertex};
 
-use super::{Primitive, PrimitiveType, DEFAULT_COLOR};
+use super::{Geometry, Primitive, DEFAULT_COLOR};
 
 pub struct Line {
     pub start: [f32; 2],
@@ -25,74 +25,33 @@ pub(crate) fn get_line(
     end: [f32; 2],
     color: [f32; 4],
     thickness: f32,
-) -> Vec<Vertex> {
+) -> Geometry {
 
     let delta = [end[0] - start[0], end[1] - start[1]];
 
     let direction = math::normalize(delta);
 
-    let start_corner1 = [-direction[1], direction[0]];
-    let start_corner2 = [direction[1], -direction[0]];
-
-    let end_corner1 = [
-        end[0] + start_corner1[0] * thickness,
-        end[1] + start_corner1[1] * thickness,
-    ];
-    let end_corner2 = [
-        end[0] + start_corner2[0] * thickness,
-        end[1] + start_corner2[1] * thickness,
-    ];
-
-    let start_corner1 = [
-        start[0] + start_corner1[0] * thickness,
-        start[1] + start_corner1[1] * thickness,
-    ];
-    let start_corner2 = [
-        start[0] + start_corner2[0] * thickness,
-        start[1] + start_corner2[1] * thickness,
-    ];
-
-    // Add the vertices
-    vec![
-        Vertex {
-            position: start_corner1,
-            color,
-            tex_coords: [0.0, 0.0],
-        },
-        Vertex {
-            position: end_corner2,
-            color,
-            tex_coords: [0.0, 0.0],
-        },
-        Vertex {
-            position: end_corner1,
-            color,
-            tex_coords: [0.0, 0.0],
-        },
-        Vertex {
-            position: start_corner2,
-            color,
-            tex_coords: [0.0, 0.0],
-        },
-        Vertex {
-            position: end_corner2,
-            color,
-            tex_coords: [0.0, 0.0],
-        },
-        Vertex {
-            position: start_corner1,
-            color,
-            tex_coords: [0.0, 0.0],
-        },
-    ]
+    let normal = [-direction[1], direction[0]];
+    let offset = [normal[0] * thickness, normal[1] * thickness];
+
+    let corner = |position| Vertex {
+        position,
+        color,
+        tex_coords: [0.0, 0.0],
+    };
+
+    let mut geometry = Geometry::with_capacity(4, 6);
+    geometry.push_quad([
+        corner([start[0] + offset[0], start[1] + offset[1]]),
+        corner([end[0] + offset[0], end[1] + offset[1]]),
+        corner([end[0] - offset[0], end[1] - offset[1]]),
+        corner([start[0] - offset[0], start[1] - offset[1]]),
+    ]);
+    geometry
 }
 
-impl Primitive for Line {
-    fn get_type(&self) -> PrimitiveType {
-        PrimitiveType::Line
-    }
-
-    fn get_vertices(&self) -> Vec<Vertex> {
+impl Primitive<'_> for Line {
+    fn get_geometry(&self) -> Geometry {
         get_line(self.start, self.end, self.color, self.thickness)
     }
 }
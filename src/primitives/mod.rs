@@ -3,32 +3,135 @@ pub mod text;
 pub mod line;
 pub mod circle;
 pub mod triangle;
+pub mod instanced;
+pub mod stats_panel;
 
-pub use text::Text;
+pub use text::{Positioning, Text};
 pub use line::Line;
 pub use rectangle::Rectangle;
 pub use circle::Circle;
 pub use triangle::Triangle;
+pub use instanced::{Instanced, ShapeInstance};
+pub use stats_panel::StatsPanel;
 
-use crate::Vertex;
+use crate::{font::Font, texture::Texture2D, Overlay, Vertex};
 
 pub const DEFAULT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum PrimitiveType {
-    Rectangle,
-    Text,
-    Circle,
-    Triangle,
-    Line,
+/// Indexed triangles. Every three indices into `vertices` form a triangle.
+#[derive(Debug, Clone, Default)]
+pub struct Geometry {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
 }
 
-pub trait Primitive {
-    fn get_vertices(&self) -> Vec<Vertex>;
-    fn get_type(&self) -> PrimitiveType;
+impl Geometry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_capacity(vertices: usize, indices: usize) -> Self {
+        Self {
+            vertices: Vec::with_capacity(vertices),
+            indices: Vec::with_capacity(indices),
+        }
+    }
+
+    /// Builds geometry from an unindexed triangle list.
+    pub fn from_triangles(vertices: Vec<Vertex>) -> Self {
+        let indices = (0..vertices.len() as u32).collect();
+        Self { vertices, indices }
+    }
+
+    /// Adds a quad, its corners given in clockwise or counter-clockwise order.
+    pub fn push_quad(&mut self, corners: [Vertex; 4]) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&corners);
+        self.indices
+            .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    /// Appends another geometry, offsetting its indices past the existing vertices.
+    pub fn append(&mut self, other: &Geometry) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + base));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// Something that can be added to a `Frame`.
+///
+/// `'a` is the lifetime of the frame, which textures and fonts used by the primitive have to outlive.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// struct Icon<'a> {
+///     texture: &'a Texture2D,
+///     position: [f32; 2],
+/// }
+///
+/// impl<'a> Primitive<'a> for Icon<'a> {
+///     fn get_geometry(&self) -> Geometry {
+///         Rectangle::new().position(self.position).dimensions([16.0, 16.0]).get_geometry()
+///     }
+///
+///     fn get_texture(&self) -> Option<&'a Texture2D> {
+///         Some(self.texture)
+///     }
+/// }
+/// ```
+pub trait Primitive<'a> {
+    /// Fills in whatever the primitive left up to the frame, like the font of text without one.
+    ///
+    /// Called when the primitive is added, before anything else.
+    fn resolve(&mut self, _resolver: &Resolver<'a>) {}
+
+    /// Returns the triangles to draw, in the frame's local coordinates.
+    fn get_geometry(&self) -> Geometry;
+
+    /// Returns the texture sampled with the geometry's texture coordinates, or None for plain colors.
+    fn get_texture(&self) -> Option<&'a Texture2D> {
+        None
+    }
+
+    /// Returns the font the primitive draws with, if any. Its texture is used when `get_texture` returns None.
+    fn get_font(&self) -> Option<&'a Font> {
+        None
+    }
+}
+
+/// What a primitive can look up while it's being added to a frame.
+pub struct Resolver<'a> {
+    overlay: &'a Overlay,
+}
+
+impl<'a> Resolver<'a> {
+    pub(crate) fn new(overlay: &'a Overlay) -> Self {
+        Self { overlay }
+    }
+
+    /// Returns the font on top of the overlay's font stack.
+    pub fn current_font(&self) -> Option<&'a Font> {
+        self.overlay.current_font()
+    }
+
+    /// Returns the font added with `id`.
+    pub fn font(&self, id: usize) -> Option<&'a Font> {
+        self.overlay.fonts.get(&id)
+    }
+
+    pub fn overlay(&self) -> &'a Overlay {
+        self.overlay
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct Outline {
     pub thickness: f32,
     pub color: [f32; 4],
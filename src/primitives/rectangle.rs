@@ -1,12 +1,13 @@
 use crate::{texture::Texture2D, Vertex};
 
-use super::{Outline, Primitive, PrimitiveType, DEFAULT_COLOR};
+use super::{Geometry, Instanced, Outline, Primitive, ShapeInstance, DEFAULT_COLOR};
 
-#[allow(unused)]
 pub struct Rectangle<'a> {
     color: [f32; 4],
     dimensions: [f32; 2],
     position: [f32; 2],
+    /// Reaches `thickness` to either side of the edges, like the border of a `Circle`.
+    /// Not drawn for textured rectangles, whose vertices all sample the texture.
     border: Option<Outline>,
     pub texture: Option<&'a Texture2D>
 }
@@ -64,49 +65,95 @@ impl<'a> Rectangle<'a> {
     }
 }
 
-impl <'a>Primitive for Rectangle<'a> {
-    fn get_vertices(&self) -> Vec<Vertex> {
-        vec![
-            Vertex {
-                position: [self.position[0], self.position[1]],
-                color: self.color,
-                tex_coords: [0.0, 0.0],
-            },
-            Vertex {
-                position: [self.position[0] + self.dimensions[0], self.position[1]],
-                color: self.color,
-                tex_coords: [1.0, 0.0],
-            },
-            Vertex {
-                position: [
-                    self.position[0] + self.dimensions[0],
-                    self.position[1] + self.dimensions[1],
-                ],
-                color: self.color,
-                tex_coords: [1.0, 1.0],
-            },
-            Vertex {
-                position: [self.position[0], self.position[1]],
-                color: self.color,
-                tex_coords: [0.0, 0.0],
-            },
-            Vertex {
-                position: [self.position[0], self.position[1] + self.dimensions[1]],
-                color: self.color,
-                tex_coords: [0.0, 1.0],
-            },
-            Vertex {
-                position: [
-                    self.position[0] + self.dimensions[0],
-                    self.position[1] + self.dimensions[1],
-                ],
-                color: self.color,
-                tex_coords: [1.0, 1.0],
+impl<'a> Primitive<'a> for Rectangle<'a> {
+    fn get_geometry(&self) -> Geometry {
+        let [x, y] = self.position;
+        let [w, h] = self.dimensions;
+        let corner = |position, tex_coords| Vertex {
+            position,
+            color: self.color,
+            tex_coords,
+        };
+
+        let mut geometry = Geometry::with_capacity(4, 6);
+        geometry.push_quad([
+            corner([x, y], [0.0, 0.0]),
+            corner([x + w, y], [1.0, 0.0]),
+            corner([x + w, y + h], [1.0, 1.0]),
+            corner([x, y + h], [0.0, 1.0]),
+        ]);
+
+        if let (Some(border), None) = (self.border, self.texture) {
+            // a frame of four quads between the outer and inner corners, which may meet in
+            // the middle when the border is thicker than the rectangle
+            let t = border.thickness;
+            let inset = |extent: f32| t.min(extent / 2.0);
+            let (inset_x, inset_y) = (inset(w), inset(h));
+            let outer = [
+                [x - t, y - t],
+                [x + w + t, y - t],
+                [x + w + t, y + h + t],
+                [x - t, y + h + t],
+            ];
+            let inner = [
+                [x + inset_x, y + inset_y],
+                [x + w - inset_x, y + inset_y],
+                [x + w - inset_x, y + h - inset_y],
+                [x + inset_x, y + h - inset_y],
+            ];
+            let base = geometry.vertices.len() as u32;
+            for position in outer.into_iter().chain(inner) {
+                geometry.vertices.push(Vertex {
+                    position,
+                    color: border.color,
+                    tex_coords: [0.0, 0.0],
+                });
+            }
+            for i in 0..4 {
+                let next = (i + 1) % 4;
+                geometry.indices.extend_from_slice(&[
+                    base + i,
+                    base + next,
+                    base + 4 + next,
+                    base + i,
+                    base + 4 + next,
+                    base + 4 + i,
+                ]);
             }
-        ]
+        }
+        geometry
+    }
+
+    fn get_texture(&self) -> Option<&'a Texture2D> {
+        self.texture
     }
+}
 
-    fn get_type(&self) -> super::PrimitiveType {
-        PrimitiveType::Rectangle
+impl Instanced for Rectangle<'_> {
+    fn get_instance(&self) -> Option<ShapeInstance> {
+        if self.texture.is_some() {
+            return None;
+        }
+        let Some(border) = self.border else {
+            return Some(ShapeInstance {
+                origin: self.position,
+                size: self.dimensions,
+                color: self.color,
+                ..Default::default()
+            });
+        };
+
+        // instance borders are drawn inside the edge, so the shape grows to the border's outside
+        let t = border.thickness;
+        let [x, y] = self.position;
+        let [w, h] = self.dimensions;
+        Some(ShapeInstance {
+            origin: [x - t, y - t],
+            size: [w + t * 2.0, h + t * 2.0],
+            color: self.color,
+            border_color: border.color,
+            radius: 0.0,
+            border: t * 2.0,
+        })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,100 @@
+use crate::{font::Font, stats::FrameStats};
+
+use super::{Geometry, Positioning, Primitive, Resolver, Text, DEFAULT_COLOR};
+
+/// Debug text showing the statistics of a frame, with a drop shadow so it stays readable
+/// over anything.
+///
+/// A frame's statistics are only complete once it's drawn, so draw those of the previous one.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut frame = overlay.new_frame();
+/// frame.add(StatsPanel::new(overlay.stats()).position([10.0, 10.0]));
+/// ```
+#[derive(Clone)]
+pub struct StatsPanel<'a> {
+    pub stats: FrameStats,
+    /// Top left corner.
+    pub position: [f32; 2],
+    pub text_size: f32,
+    pub font: Option<&'a Font>,
+    pub color: [f32; 4],
+    pub shadow_color: [f32; 4],
+}
+
+impl<'a> StatsPanel<'a> {
+    pub fn new(stats: FrameStats) -> Self {
+        Self {
+            stats,
+            position: [0.0, 0.0],
+            text_size: 14.0,
+            font: None,
+            color: DEFAULT_COLOR,
+            shadow_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn position(self, position: [f32; 2]) -> Self {
+        Self { position, ..self }
+    }
+
+    pub fn size(self, text_size: f32) -> Self {
+        Self { text_size, ..self }
+    }
+
+    pub fn font(self, font: &'a Font) -> Self {
+        Self {
+            font: Some(font),
+            ..self
+        }
+    }
+
+    pub fn color(self, color: [f32; 4]) -> Self {
+        Self { color, ..self }
+    }
+
+    pub fn shadow_color(self, shadow_color: [f32; 4]) -> Self {
+        Self {
+            shadow_color,
+            ..self
+        }
+    }
+
+    fn text(&self, position: [f32; 2], color: [f32; 4]) -> Text<'a> {
+        // placed by the first baseline, about a line below the top, since text is otherwise
+        // placed by its bounds, which change with the numbers
+        let baseline = [position[0], position[1] + self.text_size];
+        Text {
+            font: self.font,
+            ..Text::new(self.stats.to_string())
+                .position(baseline)
+                .size(self.text_size)
+                .color(color)
+                .offset([0.0, 1.0])
+                .positioning(Positioning::PixelSnap)
+        }
+    }
+}
+
+impl<'a> Primitive<'a> for StatsPanel<'a> {
+    /// Panels without a font use the one on top of the overlay's font stack.
+    fn resolve(&mut self, resolver: &Resolver<'a>) {
+        if self.font.is_none() {
+            self.font = resolver.current_font();
+        }
+    }
+
+    fn get_geometry(&self) -> Geometry {
+        let [x, y] = self.position;
+        // the shadow goes first, so the text is drawn over it
+        let mut geometry = self.text([x + 1.0, y + 1.0], self.shadow_color).get_geometry();
+        geometry.append(&self.text(self.position, self.color).get_geometry());
+        geometry
+    }
+
+    fn get_font(&self) -> Option<&'a Font> {
+        self.font
+    }
+}
@@ -1,6 +1,20 @@
 use crate::{font::Font, Vertex};
 
-use super::{Outline, Primitive, PrimitiveType, DEFAULT_COLOR};
+use super::{Geometry, Outline, Primitive, Resolver, DEFAULT_COLOR};
+
+/// How glyph quads are placed relative to the pixel grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Positioning {
+    /// Glyphs are placed at their exact, possibly fractional, positions.
+    #[default]
+    Fractional,
+    /// Baselines and glyph origins are rounded to whole pixels. Sharpest, but spacing is slightly uneven.
+    PixelSnap,
+    /// Baselines are rounded to whole pixels, and each glyph uses the atlas copy rasterized
+    /// at the closest horizontal subpixel offset. Only fonts loaded with
+    /// `FontOptions::subpixel_positions` above 1 have such copies, others are pixel snapped.
+    Subpixel,
+}
 
 #[derive(Clone)]
 pub struct Text<'a> {
@@ -12,6 +26,7 @@ pub struct Text<'a> {
     pub shadow: Option<Outline>,
     pub offset: [f32; 2],
     pub line_height: f32,
+    pub positioning: Positioning,
 }
 
 impl<'a> Default for Text<'a> {
@@ -25,6 +40,7 @@ impl<'a> Default for Text<'a> {
             shadow: Default::default(),
             offset: Default::default(),
             line_height: 2.0,
+            positioning: Positioning::default(),
         }
     }
 }
@@ -73,6 +89,21 @@ impl<'a> Text<'a> {
     pub fn offset(self, offset: [f32; 2]) -> Self {
         Self { offset, ..self }
     }
+
+    pub fn positioning(self, positioning: Positioning) -> Self {
+        Self {
+            positioning,
+            ..self
+        }
+    }
+
+    /// Shorthand for `positioning(Positioning::PixelSnap)`.
+    pub fn pixel_snap(self, pixel_snap: bool) -> Self {
+        if pixel_snap {
+            return self.positioning(Positioning::PixelSnap);
+        }
+        self.positioning(Positioning::Fractional)
+    }
 }
 
 pub fn calc_text_size(text: impl Into<String>, font: &Font, text_size: f32) -> [f32; 2] {
@@ -81,10 +112,10 @@ pub fn calc_text_size(text: impl Into<String>, font: &Font, text_size: f32) -> [
 
     let atlas = &font.atlas;
 
-    let mut min_x = std::f32::MAX;
-    let mut min_y = std::f32::MAX;
-    let mut max_x = std::f32::MIN;
-    let mut max_y = std::f32::MIN;
+    let mut min_x = f32::MAX;
+    let mut min_y = f32::MAX;
+    let mut max_x = f32::MIN;
+    let mut max_y = f32::MIN;
 
     for c in text.into().chars() {
         let glyph = atlas.get_glyph(c).unwrap();
@@ -124,17 +155,33 @@ pub fn calc_text_size(text: impl Into<String>, font: &Font, text_size: f32) -> [
     [width, height]
 }
 
-impl<'a> Primitive for Text<'a> {
-    fn get_vertices(&self) -> Vec<Vertex> {
+impl<'a> Primitive<'a> for Text<'a> {
+    /// Text without a font uses the one on top of the overlay's font stack.
+    fn resolve(&mut self, resolver: &Resolver<'a>) {
+        if self.font.is_none() {
+            self.font = resolver.current_font();
+        }
+    }
+
+    fn get_geometry(&self) -> Geometry {
         let text = &self.text;
-        let position = self.position;
+        let snap = self.positioning != Positioning::Fractional;
+        let position = if snap {
+            [self.position[0].round(), self.position[1].round()]
+        } else {
+            self.position
+        };
         let mut x = position[0];
         let mut y = position[1];
 
-        let atlas = &self.font.unwrap().atlas;
-        let mut buffer = Vec::with_capacity(text.len() * 6);
+        let Some(font) = self.font else {
+            return Geometry::new();
+        };
+        let atlas = &font.atlas;
+        let mut geometry = Geometry::with_capacity(text.len() * 4, text.len() * 6);
 
         let color = self.color;
+        let positions = atlas.options.subpixel_positions.max(1) as f32;
 
         for c in text.chars() {
             let scale = self.text_size / atlas.font_size;
@@ -142,13 +189,28 @@ impl<'a> Primitive for Text<'a> {
             if c == '\n' {
                 x = position[0];
                 y += self.line_height + self.text_size;
+                if snap {
+                    y = y.round();
+                }
                 continue;
             }
 
-            let glyph = atlas.get_glyph(c).unwrap();
-
-            let x2 = x + glyph.bitmap_left * scale;
-            let y2 = -y + glyph.bitmap_top * scale;
+            let (glyph, origin_x) = match self.positioning {
+                Positioning::Subpixel => {
+                    // quantize the pen to the offsets the atlas was rasterized at
+                    let pen = (x * positions).round() / positions;
+                    let origin = pen.floor();
+                    (atlas.get_glyph_at(c, pen - origin).unwrap(), origin)
+                }
+                _ => (atlas.get_glyph(c).unwrap(), x),
+            };
+
+            let mut x2 = origin_x + glyph.bitmap_left * scale;
+            let mut y2 = -y + glyph.bitmap_top * scale;
+            if snap {
+                x2 = x2.round();
+                y2 = y2.round();
+            }
             let w = glyph.bitmap_width * scale;
             let h = glyph.bitmap_height * scale;
 
@@ -159,60 +221,30 @@ impl<'a> Primitive for Text<'a> {
                 continue;
             }
 
-            buffer.push(Vertex {
-                position: [x2, -y2],
-                color,
-                tex_coords: [glyph.texture_x, 0.0],
-            });
-            buffer.push(Vertex {
-                position: [x2 + w, -y2],
-                color,
-                tex_coords: [
-                    glyph.texture_x + glyph.bitmap_width / atlas.texture_dimensions.0 as f32,
-                    0.0,
-                ],
-            });
-            buffer.push(Vertex {
-                position: [x2, -y2 + h],
-                color,
-                tex_coords: [
-                    glyph.texture_x,
-                    glyph.bitmap_height / atlas.texture_dimensions.1 as f32,
-                ],
-            });
-
-            buffer.push(Vertex {
-                position: [x2 + w, -y2],
-                color,
-                tex_coords: [
-                    glyph.texture_x + glyph.bitmap_width / atlas.texture_dimensions.0 as f32,
-                    0.0,
-                ],
-            });
-            buffer.push(Vertex {
-                position: [x2, -y2 + h],
-                color,
-                tex_coords: [
-                    glyph.texture_x,
-                    glyph.bitmap_height / atlas.texture_dimensions.1 as f32,
-                ],
-            });
-            buffer.push(Vertex {
-                position: [x2 + w, -y2 + h],
+            let u1 = glyph.texture_x;
+            let u2 = glyph.texture_x + glyph.bitmap_width / atlas.texture_dimensions.0 as f32;
+            let v1 = glyph.texture_y;
+            let v2 = glyph.texture_y + glyph.bitmap_height / atlas.texture_dimensions.1 as f32;
+
+            let corner = |position, tex_coords| Vertex {
+                position,
                 color,
-                tex_coords: [
-                    glyph.texture_x + glyph.bitmap_width / atlas.texture_dimensions.0 as f32,
-                    glyph.bitmap_height / atlas.texture_dimensions.1 as f32,
-                ],
-            });
+                tex_coords,
+            };
+            geometry.push_quad([
+                corner([x2, -y2], [u1, v1]),
+                corner([x2 + w, -y2], [u2, v1]),
+                corner([x2 + w, -y2 + h], [u2, v2]),
+                corner([x2, -y2 + h], [u1, v2]),
+            ]);
         }
 
-        let mut min_x = std::f32::MAX;
-        let mut min_y = std::f32::MAX;
-        let mut max_x = std::f32::MIN;
-        let mut max_y = std::f32::MIN;
+        let mut min_x = f32::MAX;
+        let mut min_y = f32::MAX;
+        let mut max_x = f32::MIN;
+        let mut max_y = f32::MIN;
 
-        for vertex in &buffer {
+        for vertex in &geometry.vertices {
             min_x = min_x.min(vertex.position[0]);
             min_y = min_y.min(vertex.position[1]);
             max_x = max_x.max(vertex.position[0]);
@@ -222,15 +254,21 @@ impl<'a> Primitive for Text<'a> {
         let width = max_x - min_x;
         let height = max_y - min_y;
 
-        for vertex in &mut buffer {
-            vertex.position[0] -= width * self.offset[0];
-            vertex.position[1] -= height * (self.offset[1] - 1.0);
+        let mut shift = [width * self.offset[0], height * (self.offset[1] - 1.0)];
+        if snap {
+            // a fractional shift would undo the snapping above
+            shift = [shift[0].round(), shift[1].round()];
+        }
+
+        for vertex in &mut geometry.vertices {
+            vertex.position[0] -= shift[0];
+            vertex.position[1] -= shift[1];
         }
 
-        buffer
+        geometry
     }
 
-    fn get_type(&self) -> PrimitiveType {
-        PrimitiveType::Text
+    fn get_font(&self) -> Option<&'a Font> {
+        self.font
     }
 }
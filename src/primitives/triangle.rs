@@ -1,6 +1,6 @@
 use crate::Vertex;
 
-use super::{Primitive, PrimitiveType};
+use super::{Geometry, Primitive};
 
 pub struct Triangle {
     pub vertices: [Vertex; 3],
@@ -12,13 +12,12 @@ impl Default for Triangle {
     }
 }
 
-impl Primitive for Triangle {
-    fn get_type(&self) -> PrimitiveType {
-        PrimitiveType::Triangle
-    }
-
-    fn get_vertices(&self) -> Vec<Vertex> {
-        self.vertices.to_vec()
+impl Primitive<'_> for Triangle {
+    fn get_geometry(&self) -> Geometry {
+        Geometry {
+            vertices: self.vertices.to_vec(),
+            indices: vec![0, 1, 2],
+        }
     }
 }
 
@@ -0,0 +1,89 @@
+use std::{
+    rc::{Rc, Weak},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::frame::Frame;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Draws frames onto a target.
+///
+/// Frames only hold backend-neutral draw commands: vertices, indices, textures, clip rectangles
+/// and blend modes. They are built without a renderer and can be drawn by any of them, like
+/// `glium_renderer::GliumRenderer`, `software::SoftwareRenderer` or one of your own.
+///
+/// Renderers keep their own copies of textures and draw lists, keyed by `ResourceHandle::id`,
+/// and can drop them once `ResourceHandle::is_alive` returns false.
+///
+/// # Example
+///
+/// ```rust
+/// # use image::RgbaImage;
+/// # use overlaylib::{
+/// #     primitives::Rectangle, renderer::Renderer, software::SoftwareRenderer, Overlay,
+/// # };
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let overlay = Overlay::new().expect("failed to load the default font");
+/// let mut renderer = SoftwareRenderer::new();
+///
+/// let mut frame = overlay.new_frame();
+/// frame.add(Rectangle::new().dimensions([100.0, 100.0]));
+///
+/// let mut image = RgbaImage::new(800, 600);
+/// renderer.render(&mut image, &frame)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Renderer<Target: ?Sized> {
+    type Error;
+
+    /// Draws every command of `frame` on top of what `target` already holds.
+    fn render(&mut self, target: &mut Target, frame: &Frame<'_>) -> Result<(), Self::Error>;
+}
+
+/// Identity of data renderers keep copies of, like textures and recorded draw lists.
+#[derive(Clone)]
+pub(crate) struct Resource {
+    id: u64,
+    alive: Rc<()>,
+}
+
+impl Resource {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            alive: Rc::new(()),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn handle(&self) -> ResourceHandle {
+        ResourceHandle {
+            id: self.id,
+            alive: Rc::downgrade(&self.alive),
+        }
+    }
+}
+
+/// Refers to a texture or a recorded draw list without keeping it alive.
+#[derive(Debug, Clone)]
+pub struct ResourceHandle {
+    id: u64,
+    alive: Weak<()>,
+}
+
+impl ResourceHandle {
+    /// Returns an identifier unique to the resource for the lifetime of the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns whether the resource still exists. Copies of dropped resources can be freed.
+    pub fn is_alive(&self) -> bool {
+        self.alive.strong_count() > 0
+    }
+}
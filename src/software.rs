@@ -0,0 +1,457 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use image::RgbaImage;
+
+use crate::{
+    frame::{BlendMode, DrawCommand, Frame},
+    primitives::ShapeInstance,
+    renderer::{Renderer, ResourceHandle},
+    texture::Texture2D,
+    OverlayError, Rect, Vertex,
+};
+
+type Color = [f32; 4];
+
+/// Renders frames on the CPU into an image, for screenshots and layout checks on machines
+/// without a GPU to draw with.
+///
+/// The output follows `glium_renderer::GliumRenderer` closely, down to the premultiplied alpha and the blend
+/// modes, but isn't identical to the pixel. Materials can't run on the CPU, so primitives drawn
+/// with one are drawn as if they had none.
+///
+/// Textures are converted to floats the first time they are drawn, and the copies are kept until
+/// the textures are dropped or `clear_cache` is called.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut renderer = SoftwareRenderer::new();
+/// let image = renderer.render_image(&frame, 800, 600);
+/// image.save("frame.png")?;
+/// ```
+#[derive(Default)]
+pub struct SoftwareRenderer {
+    /// Float copies of the textures drawn so far, keyed by `Texture2D::id`.
+    textures: HashMap<u64, (ResourceHandle, Image)>,
+}
+
+impl SoftwareRenderer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Forgets the copies of every texture drawn so far.
+    pub fn clear_cache(&mut self) {
+        self.textures.clear();
+    }
+
+    /// Renders a frame into a new, transparent `width` x `height` image.
+    ///
+    /// The image has premultiplied alpha, like what `GliumRenderer` outputs.
+    pub fn render_image(&mut self, frame: &Frame<'_>, width: u32, height: u32) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        self.draw(frame, &mut image);
+        image
+    }
+
+    /// Draws a frame on top of an image, whose colors are taken as premultiplied.
+    pub fn draw(&mut self, frame: &Frame<'_>, target: &mut RgbaImage) {
+        self.textures.retain(|_, (handle, _)| handle.is_alive());
+        let mut canvas = Canvas::from_image(target);
+        for command in frame.commands() {
+            match command {
+                DrawCommand::Immediate(buffer) => {
+                    let texture = buffer.texture.map(|texture| self.texture(texture));
+                    let shader = Shader {
+                        texture: texture.map(|image| (image, buffer.subpixel)),
+                        repeat: wraps(&buffer.vertices),
+                        blend: buffer.blend,
+                    };
+                    canvas.draw_triangles(&buffer.vertices, &buffer.indices, buffer.clip, &shader);
+                }
+                DrawCommand::Instanced(buffer) => {
+                    for instance in &buffer.instances {
+                        canvas.draw_instance(instance, buffer.clip, buffer.blend);
+                    }
+                }
+                DrawCommand::Retained {
+                    batch,
+                    offset,
+                    clip,
+                } => {
+                    let mut vertices = batch.vertices.clone();
+                    for vertex in &mut vertices {
+                        vertex.position[0] += offset[0];
+                        vertex.position[1] += offset[1];
+                    }
+                    let texture = batch.texture.as_ref().map(|texture| self.texture(texture));
+                    let shader = Shader {
+                        texture: texture.map(|image| (image, batch.subpixel)),
+                        repeat: wraps(&vertices),
+                        blend: batch.blend,
+                    };
+                    canvas.draw_triangles(&vertices, &batch.indices, *clip, &shader);
+                }
+            }
+        }
+        canvas.store(target);
+    }
+
+    /// Draws a frame into a render target created with `Texture2D::render_target`, replacing its
+    /// contents. Frames drawn by this renderer afterwards can draw the texture like any other.
+    ///
+    /// Returns `OverlayError::NotRenderTarget` if `texture` isn't a render target, like
+    /// `GliumRenderer::render_to_texture`.
+    pub fn render_to_texture(
+        &mut self,
+        texture: &Texture2D,
+        frame: &Frame<'_>,
+    ) -> Result<(), OverlayError> {
+        if !texture.is_render_target() {
+            return Err(OverlayError::NotRenderTarget);
+        }
+        // like on the GPU, a frame drawing the texture into itself draws nothing
+        self.textures.remove(&texture.id());
+        let (width, height) = texture.dimensions();
+        let image = self.render_image(frame, width, height);
+        // the top row of the image is drawn at the top, like texture coordinate 0
+        let image = Image {
+            width,
+            height,
+            texels: image.pixels().map(|pixel| to_color(&pixel.0)).collect(),
+            premultiplied: true,
+        };
+        self.textures.insert(texture.id(), (texture.handle(), image));
+        Ok(())
+    }
+
+    fn texture(&mut self, texture: &Texture2D) -> &Image {
+        let (_, image) = self
+            .textures
+            .entry(texture.id())
+            .or_insert_with(|| (texture.handle(), Image::new(texture)));
+        image
+    }
+}
+
+impl Renderer<RgbaImage> for SoftwareRenderer {
+    type Error = Infallible;
+
+    fn render(&mut self, target: &mut RgbaImage, frame: &Frame<'_>) -> Result<(), Infallible> {
+        self.draw(frame, target);
+        Ok(())
+    }
+}
+
+/// A texture's texels as floats, from the bottom row up like in GL.
+struct Image {
+    width: u32,
+    height: u32,
+    texels: Vec<Color>,
+    premultiplied: bool,
+}
+
+impl Image {
+    /// Render targets that weren't drawn into yet are empty, which samples as transparent.
+    fn new(texture: &Texture2D) -> Self {
+        let Some(pixels) = texture.pixels() else {
+            return Self {
+                width: 0,
+                height: 0,
+                texels: vec![],
+                premultiplied: true,
+            };
+        };
+        let (width, height) = texture.dimensions();
+        Self {
+            width,
+            height,
+            texels: pixels.chunks_exact(4).map(to_color).collect(),
+            premultiplied: texture.premultiplied,
+        }
+    }
+
+    /// Samples with linear filtering, either repeating the texture or clamping to its edges.
+    fn sample(&self, [u, v]: [f32; 2], repeat: bool) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return [0.0; 4];
+        }
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let texel = |x: f32, y: f32| {
+            let (x, y) = if repeat {
+                ((x as i64).rem_euclid(width), (y as i64).rem_euclid(height))
+            } else {
+                (
+                    (x as i64).clamp(0, width - 1),
+                    (y as i64).clamp(0, height - 1),
+                )
+            };
+            self.texels[(y * width + x) as usize]
+        };
+        let bottom = mix(texel(x0, y0), texel(x0 + 1.0, y0), fx);
+        let top = mix(texel(x0, y0 + 1.0), texel(x0 + 1.0, y0 + 1.0), fx);
+        mix(bottom, top, fy)
+    }
+}
+
+/// How the fragments of a batch are colored and blended.
+struct Shader<'i> {
+    /// The texture sampled, and whether it holds subpixel coverage.
+    texture: Option<(&'i Image, bool)>,
+    /// Whether the texture repeats, which is only the case when the texture coordinates go past it.
+    /// Otherwise it's clamped, like when drawn from the atlas.
+    repeat: bool,
+    blend: BlendMode,
+}
+
+impl Shader<'_> {
+    fn shade(&self, target: &mut Color, tex_coords: [f32; 2], color: Color) {
+        let mut tint = color;
+        match self.texture {
+            // each channel is blended by its own coverage, whatever the blend mode
+            Some((image, true)) => {
+                let coverage = image.sample(tex_coords, self.repeat);
+                for channel in 0..3 {
+                    let weight = coverage[channel] * tint[3];
+                    target[channel] = tint[channel] * weight + target[channel] * (1.0 - weight);
+                }
+                let weight = coverage[3] * tint[3];
+                target[3] = weight + target[3] * (1.0 - weight);
+            }
+            Some((image, false)) => {
+                let mut texel = image.sample(tex_coords, self.repeat);
+                if !image.premultiplied {
+                    texel = premultiply(texel);
+                }
+                if self.blend != BlendMode::Premultiplied {
+                    tint = premultiply(tint);
+                }
+                let source = std::array::from_fn(|channel| texel[channel] * tint[channel]);
+                blend(target, source, self.blend);
+            }
+            None => {
+                if self.blend != BlendMode::Premultiplied {
+                    tint = premultiply(tint);
+                }
+                blend(target, tint, self.blend);
+            }
+        }
+    }
+}
+
+/// The target's pixels as floats, from the top row down, so that blending doesn't lose precision.
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Canvas {
+    fn from_image(image: &RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.as_raw().chunks_exact(4).map(to_color).collect(),
+        }
+    }
+
+    fn store(&self, image: &mut RgbaImage) {
+        for (pixel, color) in image.pixels_mut().zip(&self.pixels) {
+            pixel.0 = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+
+    /// Returns the pixels `clip` covers as (left, top, right, bottom), with the right and bottom excluded.
+    fn bounds(&self, clip: Option<Rect>) -> (i64, i64, i64, i64) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        match clip {
+            // rounded outwards, like the scissor rectangle
+            Some(clip) => (
+                (clip[0][0].floor() as i64).clamp(0, width),
+                (clip[0][1].floor() as i64).clamp(0, height),
+                (clip[1][0].ceil() as i64).clamp(0, width),
+                (clip[1][1].ceil() as i64).clamp(0, height),
+            ),
+            None => (0, 0, width, height),
+        }
+    }
+
+    fn draw_triangles(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[u32],
+        clip: Option<Rect>,
+        shader: &Shader,
+    ) {
+        let bounds = self.bounds(clip);
+        for triangle in indices.chunks_exact(3) {
+            let corners =
+                [triangle[0], triangle[1], triangle[2]].map(|index| vertices.get(index as usize));
+            if let [Some(a), Some(b), Some(c)] = corners {
+                self.draw_triangle([a, b, c], bounds, shader);
+            }
+        }
+    }
+
+    fn draw_triangle(
+        &mut self,
+        [a, b, c]: [&Vertex; 3],
+        bounds: (i64, i64, i64, i64),
+        shader: &Shader,
+    ) {
+        let area = edge(a.position, b.position, c.position);
+        if area == 0.0 {
+            return;
+        }
+        // wound the same way for every triangle, so the fill rule below holds
+        let (b, c, area) = if area < 0.0 {
+            (c, b, -area)
+        } else {
+            (b, c, area)
+        };
+
+        let xs = [a.position[0], b.position[0], c.position[0]];
+        let ys = [a.position[1], b.position[1], c.position[1]];
+        let (left, top, right, bottom) = bounds;
+        let left = (xs.iter().copied().fold(f32::MAX, f32::min).floor() as i64).max(left);
+        let top = (ys.iter().copied().fold(f32::MAX, f32::min).floor() as i64).max(top);
+        let right = (xs.iter().copied().fold(f32::MIN, f32::max).ceil() as i64).min(right);
+        let bottom = (ys.iter().copied().fold(f32::MIN, f32::max).ceil() as i64).min(bottom);
+
+        let edges = [(b, c), (c, a), (a, b)];
+        for y in top..bottom {
+            for x in left..right {
+                let point = [x as f32 + 0.5, y as f32 + 0.5];
+                let weights = edges.map(|(from, to)| edge(from.position, to.position, point));
+                // pixels right on an edge belong to only one of the triangles sharing it
+                let inside = weights.iter().zip(edges).all(|(&weight, (from, to))| {
+                    weight > 0.0 || (weight == 0.0 && owns_edge(from.position, to.position))
+                });
+                if !inside {
+                    continue;
+                }
+
+                let [wa, wb, wc] = weights.map(|weight| weight / area);
+                let tex_coords = std::array::from_fn(|i| {
+                    a.tex_coords[i] * wa + b.tex_coords[i] * wb + c.tex_coords[i] * wc
+                });
+                let color =
+                    std::array::from_fn(|i| a.color[i] * wa + b.color[i] * wb + c.color[i] * wc);
+                let pixel = &mut self.pixels[(y * self.width as i64 + x) as usize];
+                shader.shade(pixel, tex_coords, color);
+            }
+        }
+    }
+
+    /// Draws a shape instance the way the instanced program does, from its signed distance.
+    fn draw_instance(&mut self, instance: &ShapeInstance, clip: Option<Rect>, mode: BlendMode) {
+        let half_size = [instance.size[0] * 0.5, instance.size[1] * 0.5];
+        let center = [
+            instance.origin[0] + half_size[0],
+            instance.origin[1] + half_size[1],
+        ];
+        let radius = instance.radius.min(half_size[0].min(half_size[1]));
+        let (mut fill, mut border) = (instance.color, instance.border_color);
+        if mode != BlendMode::Premultiplied {
+            fill = premultiply(fill);
+            border = premultiply(border);
+        }
+
+        let (left, top, right, bottom) = self.bounds(clip);
+        // one pixel of margin around the shape for its anti-aliased edge
+        let left = ((instance.origin[0] - 1.0).floor() as i64).max(left);
+        let top = ((instance.origin[1] - 1.0).floor() as i64).max(top);
+        let right = ((instance.origin[0] + instance.size[0] + 1.0).ceil() as i64).min(right);
+        let bottom = ((instance.origin[1] + instance.size[1] + 1.0).ceil() as i64).min(bottom);
+
+        for y in top..bottom {
+            for x in left..right {
+                let local = [x as f32 + 0.5 - center[0], y as f32 + 0.5 - center[1]];
+                let distance = rounded_box(local, half_size, radius);
+                let outer = (0.5 - distance).clamp(0.0, 1.0);
+                let inner = (0.5 - distance - instance.border).clamp(0.0, 1.0);
+                let source: Color =
+                    std::array::from_fn(|i| fill[i] * inner + border[i] * (outer - inner));
+                if source[3] <= 0.0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[(y * self.width as i64 + x) as usize];
+                blend(pixel, source, mode);
+            }
+        }
+    }
+}
+
+/// Blends a premultiplied color onto a pixel, like the overlay's blending for `mode` does.
+fn blend(target: &mut Color, source: Color, mode: BlendMode) {
+    let alpha = source[3];
+    for channel in 0..3 {
+        let (s, d) = (source[channel], target[channel]);
+        target[channel] = match mode {
+            BlendMode::Normal | BlendMode::Premultiplied => s + d * (1.0 - alpha),
+            BlendMode::Additive => s + d,
+            BlendMode::Multiply => s * d + d * (1.0 - alpha),
+            BlendMode::Screen => s + d * (1.0 - s),
+        }
+        .min(1.0);
+    }
+    target[3] = (alpha + target[3] * (1.0 - alpha)).min(1.0);
+}
+
+/// Whether the texture coordinates of `vertices` rely on wrapping.
+fn wraps(vertices: &[Vertex]) -> bool {
+    vertices.iter().any(|vertex| {
+        vertex
+            .tex_coords
+            .iter()
+            .any(|coordinate| !(0.0..=1.0).contains(coordinate))
+    })
+}
+
+/// Twice the signed area of the triangle (a, b, p).
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    // always computed from the same end, so swapping a and b flips the sign without rounding differently
+    if (a[0], a[1]) > (b[0], b[1]) {
+        return -edge(b, a, p);
+    }
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether pixels centered exactly on the edge from `a` to `b` are drawn. Neighbouring triangles
+/// go along a shared edge in opposite directions, so exactly one of them owns it.
+fn owns_edge(a: [f32; 2], b: [f32; 2]) -> bool {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    dy > 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+/// Distance to the edge of a rounded rectangle, negative inside.
+fn rounded_box(p: [f32; 2], half_size: [f32; 2], radius: f32) -> f32 {
+    let q = [
+        p[0].abs() - half_size[0] + radius,
+        p[1].abs() - half_size[1] + radius,
+    ];
+    let outside = (q[0].max(0.0).powi(2) + q[1].max(0.0).powi(2)).sqrt();
+    outside + q[0].max(q[1]).min(0.0) - radius
+}
+
+fn premultiply(color: Color) -> Color {
+    [
+        color[0] * color[3],
+        color[1] * color[3],
+        color[2] * color[3],
+        color[3],
+    ]
+}
+
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+fn to_color(bytes: &[u8]) -> Color {
+    std::array::from_fn(|i| bytes[i] as f32 / 255.0)
+}
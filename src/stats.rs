@@ -0,0 +1,49 @@
+use std::{fmt, time::Duration};
+
+/// What it took to build and draw a frame, to find out why it's slow.
+///
+/// Frames count what is added to them, see `Frame::stats`. Renderers add what drawing took,
+/// like `GliumRenderer::stats` and `Overlay::stats` for the last frame drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Primitives added to the frame, including the instanced ones.
+    pub primitives: usize,
+    /// Primitives added with `Frame::add_instanced` that were drawn as instances.
+    pub instances: usize,
+    /// Vertices built on the CPU. Retained draw lists and instances don't add any.
+    pub vertices: usize,
+    pub draw_calls: usize,
+    /// Draw calls sampling another texture than the previous one did.
+    pub texture_switches: usize,
+    /// Bytes of textures and geometry sent to the GPU.
+    pub upload_bytes: usize,
+    /// CPU time spent building geometry, in `Primitive::get_geometry` and while adding it to the frame.
+    pub build_time: Duration,
+    /// CPU time the renderer spent uploading and submitting draw calls. The GPU draws
+    /// asynchronously, so this doesn't include the time it takes to execute them.
+    pub submit_time: Duration,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the total CPU time spent on the frame.
+    pub fn cpu_time(&self) -> Duration {
+        self.build_time + self.submit_time
+    }
+}
+
+/// One line per statistic, as drawn by `primitives::StatsPanel`.
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "primitives: {} ({} instanced)", self.primitives, self.instances)?;
+        writeln!(f, "vertices: {}", self.vertices)?;
+        writeln!(f, "draw calls: {}", self.draw_calls)?;
+        writeln!(f, "texture switches: {}", self.texture_switches)?;
+        writeln!(f, "uploaded: {:.1} KiB", self.upload_bytes as f64 / 1024.0)?;
+        writeln!(f, "build: {:.2} ms", self.build_time.as_secs_f64() * 1000.0)?;
+        write!(f, "submit: {:.2} ms", self.submit_time.as_secs_f64() * 1000.0)
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::HashSet,
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+};
+
+use freetype_sys::{
+    ft_sfnt_os2, FT_Get_Sfnt_Table, FT_Init_FreeType, FT_New_Face, TT_OS2, FT_STYLE_FLAG_BOLD,
+    FT_STYLE_FLAG_ITALIC,
+};
+
+use crate::font::{Face, Library};
+
+/// Common weight values, as used by CSS and the OpenType OS/2 table.
+pub mod weight {
+    pub const THIN: u16 = 100;
+    pub const LIGHT: u16 = 300;
+    pub const REGULAR: u16 = 400;
+    pub const MEDIUM: u16 = 500;
+    pub const SEMIBOLD: u16 = 600;
+    pub const BOLD: u16 = 700;
+    pub const BLACK: u16 = 900;
+}
+
+const NORMAL_WIDTH: u16 = 5;
+
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+/// A font face installed on the system.
+#[derive(Debug, Clone)]
+pub struct SystemFont {
+    pub path: PathBuf,
+    pub face_index: u32,
+    pub family: String,
+    pub weight: u16,
+    /// OpenType width class, from 1 (ultra-condensed) to 9 (ultra-expanded). 5 is normal.
+    pub width: u16,
+    pub italic: bool,
+}
+
+/// Returns the directories fonts are installed in.
+///
+/// These are the standard Linux font directories, plus any `<dir>` listed in the fontconfig configuration.
+pub fn font_directories() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+    ];
+    if let Some(data_home) = &data_home {
+        dirs.push(data_home.join("fonts"));
+    }
+    if let Some(home) = &home {
+        dirs.push(home.join(".fonts"));
+    }
+
+    let mut configs = vec![PathBuf::from("/etc/fonts/fonts.conf")];
+    if let Ok(entries) = std::fs::read_dir("/etc/fonts/conf.d") {
+        configs.extend(entries.flatten().map(|entry| entry.path()));
+    }
+
+    for config in configs {
+        let Ok(contents) = std::fs::read_to_string(&config) else {
+            continue;
+        };
+        for (attributes, dir) in config_dirs(&contents) {
+            let dir = if attributes.contains("prefix=\"xdg\"") {
+                match &data_home {
+                    Some(data_home) => data_home.join(dir),
+                    None => continue,
+                }
+            } else if let Some(rest) = dir.strip_prefix('~') {
+                match &home {
+                    Some(home) => home.join(rest.trim_start_matches('/')),
+                    None => continue,
+                }
+            } else {
+                PathBuf::from(dir)
+            };
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+
+    dirs
+}
+
+/// Extracts the attributes and contents of every `<dir>` element in a fontconfig file.
+fn config_dirs(contents: &str) -> Vec<(&str, &str)> {
+    let mut dirs = vec![];
+    let mut rest = contents;
+
+    while let Some(start) = rest.find("<dir") {
+        rest = &rest[start + 4..];
+        // skip elements like <dirname> that merely start with "dir"
+        if !rest.starts_with('>') && !rest.starts_with(' ') {
+            continue;
+        }
+        let Some(close) = rest.find('>') else { break };
+        let attributes = &rest[..close];
+        rest = &rest[close + 1..];
+        let Some(end) = rest.find("</dir>") else { break };
+        dirs.push((attributes, rest[..end].trim()));
+        rest = &rest[end..];
+    }
+
+    dirs
+}
+
+/// Lists every font face found in the system font directories.
+pub fn list() -> Vec<SystemFont> {
+    let library = unsafe {
+        let mut library = std::ptr::null_mut();
+        if FT_Init_FreeType(&mut library) != 0 {
+            return vec![];
+        }
+        Library(library)
+    };
+
+    let mut fonts = vec![];
+    let mut visited = HashSet::new();
+    for dir in font_directories() {
+        scan_directory(&library, &dir, &mut visited, &mut fonts);
+    }
+    fonts
+}
+
+/// Reads the fonts in `dir` and its subdirectories.
+///
+/// Directories are followed through symlinks, which can form loops, so every directory is
+/// only scanned once, by its canonical path.
+fn scan_directory(
+    library: &Library,
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    fonts: &mut Vec<SystemFont>,
+) {
+    let Ok(canonical) = dir.canonicalize() else {
+        return;
+    };
+    if !visited.insert(canonical) {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scan_directory(library, &path, visited, fonts);
+            continue;
+        }
+        let is_font = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| FONT_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+        if is_font {
+            read_faces(library, &path, fonts);
+        }
+    }
+}
+
+fn read_faces(library: &Library, path: &Path, fonts: &mut Vec<SystemFont>) {
+    let Ok(c_path) = CString::new(path.as_os_str().as_encoded_bytes()) else {
+        return;
+    };
+
+    let mut face_index = 0;
+    let mut num_faces = 1;
+    while face_index < num_faces {
+        let face = unsafe {
+            let mut face = std::ptr::null_mut();
+            if FT_New_Face(library.0, c_path.as_ptr(), face_index, &mut face) != 0 {
+                return;
+            }
+            Face(face)
+        };
+        num_faces = unsafe { (*face.0).num_faces };
+        if let Some(font) = unsafe { describe_face(&face, path, face_index as u32) } {
+            fonts.push(font);
+        }
+        face_index += 1;
+    }
+}
+
+/// Reads the family, weight, width and style of a face. Returns None for faces without a family name.
+unsafe fn describe_face(face: &Face, path: &Path, face_index: u32) -> Option<SystemFont> {
+    let record = &*face.0;
+    if record.family_name.is_null() {
+        return None;
+    }
+    let family = CStr::from_ptr(record.family_name).to_string_lossy().into_owned();
+    let os2 = FT_Get_Sfnt_Table(face.0, ft_sfnt_os2) as *const TT_OS2;
+    let weight = if !os2.is_null() && (*os2).usWeightClass != 0 {
+        (*os2).usWeightClass
+    } else if record.style_flags & FT_STYLE_FLAG_BOLD != 0 {
+        weight::BOLD
+    } else {
+        weight::REGULAR
+    };
+    let width = if !os2.is_null() && (*os2).usWidthClass != 0 {
+        (*os2).usWidthClass
+    } else {
+        NORMAL_WIDTH
+    };
+
+    Some(SystemFont {
+        path: path.to_path_buf(),
+        face_index,
+        family,
+        weight,
+        width,
+        italic: record.style_flags & FT_STYLE_FLAG_ITALIC != 0,
+    })
+}
+
+/// Finds the installed upright, normal width face of `family` whose weight is closest to `weight`.
+///
+/// The family name is matched case-insensitively. Returns None if no face of that family is installed.
+pub fn find(family: &str, weight: u16) -> Option<SystemFont> {
+    list()
+        .into_iter()
+        .filter(|font| font.family.eq_ignore_ascii_case(family))
+        .min_by_key(|font| {
+            (
+                font.italic,
+                font.weight.abs_diff(weight),
+                font.width.abs_diff(NORMAL_WIDTH),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use freetype_sys::FT_Init_FreeType;
+
+    use super::{config_dirs, scan_directory, weight};
+    use crate::font::Library;
+
+    #[test]
+    fn fontconfig_dirs() {
+        let config = r#"<?xml version="1.0"?>
+<!DOCTYPE fontconfig SYSTEM "urn:fontconfig:fonts.dtd">
+<fontconfig>
+	<dir>/usr/share/fonts</dir>
+	<dir prefix="xdg">fonts</dir>
+	<!-- the following element will be removed in the future -->
+	<dir>~/.fonts</dir>
+	<dirname>not a font directory</dirname>
+	<cachedir>/var/cache/fontconfig</cachedir>
+	<dir>
+		/opt/fonts
+	</dir>
+</fontconfig>"#;
+        assert_eq!(
+            config_dirs(config),
+            vec![
+                ("", "/usr/share/fonts"),
+                (" prefix=\"xdg\"", "fonts"),
+                ("", "~/.fonts"),
+                ("", "/opt/fonts"),
+            ]
+        );
+        assert!(config_dirs("<fontconfig><dir>/unterminated").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_symlink_cycle() {
+        // a font next to a subdirectory that links back to its parent
+        let dir = std::env::temp_dir().join(format!("overlaylib-fonts-{}", std::process::id()));
+        let nested = dir.join("nested");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&nested).expect("failed to create the font directory");
+        std::fs::write(
+            dir.join("NotoSansMono-Regular.ttf"),
+            include_bytes!("../assets/fonts/NotoSansMono-Regular.ttf"),
+        )
+        .expect("failed to write the font");
+        std::os::unix::fs::symlink(&dir, nested.join("parent")).expect("failed to create the link");
+
+        let library = unsafe {
+            let mut library = std::ptr::null_mut();
+            assert_eq!(FT_Init_FreeType(&mut library), 0);
+            Library(library)
+        };
+        let mut fonts = vec![];
+        scan_directory(&library, &dir, &mut HashSet::new(), &mut fonts);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].family, "Noto Sans Mono");
+        assert_eq!(fonts[0].face_index, 0);
+        assert_eq!(fonts[0].weight, weight::REGULAR);
+        assert!(!fonts[0].italic);
+    }
+}
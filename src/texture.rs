@@ -1,25 +1,90 @@
-use glium::{backend::Facade, GlObject, Texture2d};
+use std::rc::Rc;
 
+use crate::renderer::{Resource, ResourceHandle};
+
+/// An RGBA image that primitives can be drawn with.
+///
+/// The pixels stay on the CPU, renderers upload their own copy the first time they draw it.
+/// Render targets have no pixels, their contents only exist on the renderer that draws into them.
 pub struct Texture2D {
-    pub texture: glium::texture::Texture2d,
-    pub dimensions: (u32, u32),
+    /// Width and height in pixels, matching `pixels`.
+    dimensions: (u32, u32),
+    /// Whether the color channels are already multiplied by alpha. Filtering such textures doesn't
+    /// darken the edges of transparent areas.
+    pub premultiplied: bool,
+    /// Four bytes per pixel, starting with the row at texture coordinate 0. None for render targets.
+    pixels: Option<Rc<[u8]>>,
+    resource: Resource,
 }
 
 impl PartialEq for Texture2D {
     fn eq(&self, other: &Self) -> bool {
-        self.texture.get_id() == other.texture.get_id()
+        self.id() == other.id()
     }
 }
 
 #[derive(Debug)]
 pub enum TextureError {
     InvalidImage,
-    TextureCreationError(glium::texture::TextureCreationError),
+}
+
+/// Options controlling how an image is loaded into a texture.
+#[derive(Debug, Clone, Default)]
+pub struct TextureOptions {
+    /// Flips the image vertically.
+    pub reversed: bool,
+    /// Multiplies the color channels by alpha when loading.
+    pub premultiplied: bool,
+}
+
+impl TextureOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn reversed(self, reversed: bool) -> Self {
+        Self { reversed, ..self }
+    }
+
+    pub fn premultiplied(self, premultiplied: bool) -> Self {
+        Self {
+            premultiplied,
+            ..self
+        }
+    }
 }
 
 impl Texture2D {
-    pub fn new(texture: Texture2d, dimensions: (u32, u32)) -> Self {
-        Self { texture, dimensions }
+    /// Creates a texture from RGBA pixels, four bytes each, starting with the row at texture coordinate 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` doesn't hold exactly `dimensions.0 * dimensions.1` pixels.
+    pub fn new(pixels: Vec<u8>, dimensions: (u32, u32)) -> Self {
+        assert_eq!(
+            pixels.len(),
+            dimensions.0 as usize * dimensions.1 as usize * 4,
+            "pixel data doesn't match the texture dimensions"
+        );
+        Self {
+            dimensions,
+            premultiplied: false,
+            pixels: Some(pixels.into()),
+            resource: Resource::new(),
+        }
+    }
+
+    /// Creates a texture that frames can be drawn into, with a renderer's `render_to_texture`.
+    ///
+    /// It has no pixels, only the renderer that draws into it can draw with it. Until then it
+    /// is transparent. Like everything renderers output, its colors are premultiplied by alpha.
+    pub fn render_target(dimensions: (u32, u32)) -> Self {
+        Self {
+            dimensions,
+            premultiplied: true,
+            pixels: None,
+            resource: Resource::new(),
+        }
     }
 
     /// Loads a texture from a file and returns a reference to the Texture2D.
@@ -30,49 +95,20 @@ impl Texture2D {
     ///
     /// # Returns
     /// * `Result<Texture2D, TextureError>` - The result of texture creation.
-    pub fn load_from_file<F: Facade>(
-        facade: &F,
-        path: &str,
-    ) -> Result<Texture2D, TextureError> {
-        let image = image::open(path)
-            .map_err(|_| TextureError::InvalidImage)?
-            .to_rgba8();
-        let dimensions = image.dimensions();
-        let image =
-            glium::texture::RawImage2d::from_raw_rgba(image.into_raw(), dimensions);
-        let texture = glium::texture::Texture2d::new(facade, image)
-            .map_err(|e| TextureError::TextureCreationError(e))?;
-        Ok(Texture2D {
-            texture,
-            dimensions,
-        })
+    pub fn load_from_file(path: &str) -> Result<Texture2D, TextureError> {
+        Self::load_from_file_with_options(path, TextureOptions::default())
     }
 
     /// Loads a texture from a file, reverses it and returns a reference to the Texture2D.
     /// # Arguments
     ///
-    /// * `facade` - The facade to create the texture with.
     /// * `path` - The path to the texture file.
     ///
     /// # Returns
     ///
     /// * `Result<Texture2D, TextureError>` - The result of texture creation.
-    pub fn load_from_file_reversed<F: Facade>(
-        facade: &F,
-        path: &str,
-    ) -> Result<Texture2D, TextureError> {
-        let image = image::open(path)
-            .map_err(|_| TextureError::InvalidImage)?
-            .to_rgba8();
-        let dimensions = image.dimensions();
-        let image =
-            glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions);
-        let texture = glium::texture::Texture2d::new(facade, image)
-            .map_err(|e| TextureError::TextureCreationError(e))?;
-        Ok(Texture2D {
-            texture,
-            dimensions,
-        })
+    pub fn load_from_file_reversed(path: &str) -> Result<Texture2D, TextureError> {
+        Self::load_from_file_with_options(path, TextureOptions::default().reversed(true))
     }
 
     /// Loads a texture from bytes and returns a reference to the Texture2D.
@@ -84,51 +120,111 @@ impl Texture2D {
     /// # Returns
     ///
     /// * `Result<Texture2D, TextureError>` - The result of texture creation.
-    pub fn load_from_memory<F: Facade>(
-        facade: &F,
-        data: &[u8],
-    ) -> Result<Texture2D, TextureError> {
-        let image = image::load_from_memory(data)
-            .map_err(|_| TextureError::InvalidImage)?
-            .to_rgba8();
-        let dimensions = image.dimensions();
-        let image = glium::texture::RawImage2d::from_raw_rgba(image.into_raw(), dimensions);
-        let texture = glium::texture::Texture2d::new(facade, image)
-            .map_err(|e| TextureError::TextureCreationError(e))?;
-        Ok(Texture2D {
-            texture,
-            dimensions,
-        })
+    pub fn load_from_memory(data: &[u8]) -> Result<Texture2D, TextureError> {
+        Self::load_from_memory_with_options(data, TextureOptions::default())
     }
 
     /// Loads a texture from bytes, reverses it and returns a reference to the Texture2D.
     /// # Arguments
     ///
-    /// * `facade` - The facade to create the texture with.
     /// * `data` - The bytes of the texture.
     ///
     /// # Returns
     /// * `Result<Texture2D, TextureError>` - The result of texture creation.
-    pub fn load_from_memory_reversed<F: Facade>(
-        facade: &F,
+    pub fn load_from_memory_reversed(data: &[u8]) -> Result<Texture2D, TextureError> {
+        Self::load_from_memory_with_options(data, TextureOptions::default().reversed(true))
+    }
+
+    /// Loads a texture from a file, according to `options`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the texture file.
+    /// * `options` - How the image is loaded.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Texture2D, TextureError>` - The result of texture creation.
+    pub fn load_from_file_with_options(
+        path: &str,
+        options: TextureOptions,
+    ) -> Result<Texture2D, TextureError> {
+        let image = image::open(path).map_err(|_| TextureError::InvalidImage)?;
+        Ok(Self::from_image(image.to_rgba8(), options))
+    }
+
+    /// Loads a texture from bytes, according to `options`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The bytes of the texture.
+    /// * `options` - How the image is loaded.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Texture2D, TextureError>` - The result of texture creation.
+    pub fn load_from_memory_with_options(
         data: &[u8],
+        options: TextureOptions,
     ) -> Result<Texture2D, TextureError> {
-        let image = image::load_from_memory(data)
-            .map_err(|_| TextureError::InvalidImage)?
-            .to_rgba8();
+        let image = image::load_from_memory(data).map_err(|_| TextureError::InvalidImage)?;
+        Ok(Self::from_image(image.to_rgba8(), options))
+    }
+
+    fn from_image(mut image: image::RgbaImage, options: TextureOptions) -> Texture2D {
+        if options.premultiplied {
+            for pixel in image.pixels_mut() {
+                let alpha = pixel[3] as u32;
+                for channel in &mut pixel.0[..3] {
+                    *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+                }
+            }
+        }
+        // the first row of an image is its top, and texture coordinates start at the bottom
+        if options.reversed {
+            image::imageops::flip_vertical_in_place(&mut image);
+        }
         let dimensions = image.dimensions();
-        let image =
-            glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions);
-        let texture = glium::texture::Texture2d::new(facade, image)
-            .map_err(|e| TextureError::TextureCreationError(e))?;
-        Ok(Texture2D {
-            texture,
-            dimensions,
-        })
+        let mut texture = Texture2D::new(image.into_raw(), dimensions);
+        texture.premultiplied = options.premultiplied;
+        texture
+    }
+
+    /// Returns the width and height of the texture, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Returns an identifier unique to this texture for the lifetime of the process.
+    pub fn id(&self) -> u64 {
+        self.resource.id()
+    }
+
+    /// Returns a handle renderers key their copy of this texture by.
+    pub fn handle(&self) -> ResourceHandle {
+        self.resource.handle()
+    }
+
+    /// Returns the RGBA pixels, starting with the row at texture coordinate 0.
+    ///
+    /// Returns None for render targets.
+    pub fn pixels(&self) -> Option<&[u8]> {
+        self.pixels.as_deref()
+    }
+
+    /// Returns whether this texture was created with `render_target`.
+    pub fn is_render_target(&self) -> bool {
+        self.pixels.is_none()
     }
 
-    /// Returns a reference to the underlying glium texture.
-    pub fn get_gl_texture(&self) -> &Texture2d {
-        &self.texture
+    /// Returns another handle to this texture, sharing its pixels and identity, so renderers
+    /// keep their copy of it until every handle is dropped.
+    pub(crate) fn share(&self) -> Texture2D {
+        Texture2D {
+            dimensions: self.dimensions,
+            premultiplied: self.premultiplied,
+            pixels: self.pixels.clone(),
+            resource: self.resource.clone(),
+        }
     }
 }
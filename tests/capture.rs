@@ -0,0 +1,55 @@
+//! Frames captured into images, with the software renderer of `Overlay::new`.
+
+use overlaylib::{primitives::Rectangle, Overlay};
+
+/// Whether every channel is within one of the expected value, for rounding.
+fn close(actual: [u8; 4], expected: [u8; 4]) -> bool {
+    actual
+        .iter()
+        .zip(expected)
+        .all(|(actual, expected)| actual.abs_diff(expected) <= 1)
+}
+
+#[test]
+fn capture_frame_unpremultiplies() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .dimensions([20.0, 10.0])
+            .color([1.0, 0.5, 0.0, 0.5]),
+    );
+    // half transparent white over opaque blue stays opaque
+    frame.add(
+        Rectangle::new()
+            .position([0.0, 10.0])
+            .dimensions([20.0, 10.0])
+            .color([0.0, 0.0, 1.0, 1.0]),
+    );
+    frame.add(
+        Rectangle::new()
+            .position([10.0, 10.0])
+            .dimensions([10.0, 10.0])
+            .color([1.0, 1.0, 1.0, 0.5]),
+    );
+
+    let image = overlay
+        .capture_frame(&frame, (30, 20))
+        .expect("failed to capture the frame");
+    assert_eq!(image.dimensions(), (30, 20));
+
+    // the straight color of the rectangle, not the premultiplied one the renderer draws
+    let orange = image.get_pixel(5, 5).0;
+    assert!(close(orange, [255, 128, 0, 128]), "{:?}", orange);
+    assert_eq!(image.get_pixel(5, 15).0, [0, 0, 255, 255]);
+    let blended = image.get_pixel(15, 15).0;
+    assert!(close(blended, [128, 128, 255, 255]), "{:?}", blended);
+    // nothing drawn is fully transparent
+    assert_eq!(image.get_pixel(25, 5).0, [0, 0, 0, 0]);
+
+    // the frame is left as it was, and captures the same again
+    let again = overlay
+        .capture_frame(&frame, (30, 20))
+        .expect("failed to capture the frame");
+    assert_eq!(image, again);
+}
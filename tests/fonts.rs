@@ -0,0 +1,76 @@
+//! Font loading errors, checked against the bundled font.
+
+use overlaylib::{
+    font::{Font, FontError, FontOptions, MAX_ATLAS_SIZE},
+    Overlay, OverlayError,
+};
+
+const FONT: &[u8] = include_bytes!("../assets/fonts/NotoSansMono-Regular.ttf");
+
+#[test]
+fn missing_font_file() {
+    let mut overlay = Overlay::new().expect("failed to create the overlay");
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/no-such-font.ttf");
+    assert!(matches!(
+        overlay.add_font_from_file(path, 16.0, 1),
+        Err(OverlayError::FileNotFound)
+    ));
+    assert!(matches!(
+        overlay.add_font_from_file_with_options(path, 16.0, 1, FontOptions::new()),
+        Err(OverlayError::FileNotFound)
+    ));
+}
+
+#[test]
+fn atlas_too_large() {
+    // every ASCII glyph side by side is far wider than the largest texture at this size
+    match Font::new(FONT, 400.0) {
+        Err(FontError::AtlasTooLarge {
+            dimensions,
+            max_size,
+        }) => {
+            assert_eq!(max_size, MAX_ATLAS_SIZE);
+            assert!(dimensions.0 > MAX_ATLAS_SIZE || dimensions.1 > MAX_ATLAS_SIZE);
+        }
+        Err(e) => panic!("expected AtlasTooLarge, got {:?}", e),
+        Ok(_) => panic!("expected AtlasTooLarge, the font loaded"),
+    }
+
+    // so is a row for each of many subpixel offsets
+    let options = FontOptions::new().subpixel_positions(1024);
+    assert!(matches!(
+        Font::with_options(FONT, 24.0, options),
+        Err(FontError::AtlasTooLarge { .. })
+    ));
+
+    assert!(Font::new(FONT, 24.0).is_ok());
+}
+
+#[test]
+fn faces_and_variations() {
+    // the bundled font is a single, static face
+    assert!(Font::with_options(FONT, 16.0, FontOptions::new().face_index(0)).is_ok());
+    assert!(matches!(
+        Font::with_options(FONT, 16.0, FontOptions::new().face_index(1)),
+        Err(FontError::NoSuchFace {
+            face_index: 1,
+            named_instance: None,
+        })
+    ));
+    assert!(matches!(
+        Font::with_options(FONT, 16.0, FontOptions::new().face_index(3).named_instance(0)),
+        Err(FontError::NoSuchFace {
+            face_index: 3,
+            named_instance: Some(0),
+        })
+    ));
+
+    // it has no axes at all, so any of them is rejected before looking the tag up
+    for tag in [b"wght", b"zzzz"] {
+        let options = FontOptions::new().variation(tag, 700.0);
+        assert!(matches!(
+            Font::with_options(FONT, 16.0, options),
+            Err(FontError::NotVariable)
+        ));
+    }
+}
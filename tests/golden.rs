@@ -0,0 +1,465 @@
+//! Renders frames on the CPU and compares them with the images in `tests/golden`.
+//!
+//! After an intended rendering change, run `OVERLAYLIB_BLESS=1 cargo test --test golden` to
+//! update the images, and check the new ones before committing them.
+
+use std::time::Duration;
+
+use overlaylib::{
+    draw_list::DrawList,
+    font::{Font, FontOptions},
+    frame::{BlendMode, Frame, Layer},
+    golden::{self, GoldenTest},
+    primitives::{Circle, Line, Outline, Positioning, Rectangle, StatsPanel, Text},
+    software::SoftwareRenderer,
+    stats::FrameStats,
+    texture::Texture2D,
+    Overlay, OverlayError,
+};
+
+/// Allows for small differences in how FreeType versions rasterize glyphs.
+fn golden() -> GoldenTest {
+    GoldenTest::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"))
+        .tolerance(8)
+        .max_mismatched(16)
+}
+
+fn check(name: &str, frame: &Frame<'_>, width: u32, height: u32) {
+    if let Err(e) = golden().check_frame(name, frame, width, height) {
+        panic!("{}: {}", name, e);
+    }
+}
+
+#[test]
+fn text_placement() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    frame.add(Text::new("Top left gjq").position([0.0, 0.0]).size(24.0));
+    frame.add(Text::new("Fractional").position([10.5, 40.25]).size(18.0));
+    frame.add(
+        Text::new("Pixel snapped")
+            .position([10.5, 70.25])
+            .size(18.0)
+            .positioning(Positioning::PixelSnap),
+    );
+    frame.add(
+        Text::new("Centered")
+            .position([160.0, 120.0])
+            .size(24.0)
+            .centered(true),
+    );
+    frame.add(
+        Text::new("Two\nlines")
+            .position([220.0, 10.0])
+            .size(16.0)
+            .color([1.0, 0.8, 0.2, 1.0]),
+    );
+    // a quarter pixel apart, each drawn with its own glyph rasterization
+    let font = Font::with_options(
+        include_bytes!("../assets/fonts/NotoSansMono-Regular.ttf"),
+        18.0,
+        FontOptions::new().subpixel_positions(4),
+    )
+    .expect("failed to load the font");
+    for (i, x) in [10.0, 10.25, 10.5, 10.75].into_iter().enumerate() {
+        frame.add(
+            Text::new("Subpix")
+                .position([x + i as f32 * 76.0, 160.25])
+                .size(18.0)
+                .positioning(Positioning::Subpixel)
+                .font(&font),
+        );
+    }
+    check("text_placement", &frame, 320, 190);
+}
+
+#[test]
+fn textures() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    // a red and blue checkerboard with a green texel at texture coordinate 0
+    let mut pixels = Vec::new();
+    for y in 0..4 {
+        for x in 0..4 {
+            let pixel = match (x, y) {
+                (0, 0) => [0, 255, 0, 255],
+                _ if (x + y) % 2 == 0 => [255, 0, 0, 255],
+                _ => [0, 0, 255, 128],
+            };
+            pixels.extend(pixel);
+        }
+    }
+    let texture = Texture2D::new(pixels, (4, 4));
+
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .position([10.0, 10.0])
+            .dimensions([64.0, 64.0])
+            .texture(&texture),
+    );
+    frame.push_tint([1.0, 1.0, 1.0, 0.5]);
+    frame.add(
+        Rectangle::new()
+            .position([90.0, 10.0])
+            .dimensions([32.0, 32.0])
+            .texture(&texture),
+    );
+    frame.pop_tint();
+    check("textures", &frame, 140, 90);
+}
+
+#[test]
+fn shapes() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .position([10.0, 10.0])
+            .dimensions([80.0, 50.0])
+            .color([0.2, 0.4, 0.9, 1.0])
+            .border(Outline::new().thickness(2.0)),
+    );
+    frame.add(
+        Circle::new()
+            .position([150.0, 35.0])
+            .radius(25.0_f32)
+            .filled(true)
+            .color([0.9, 0.3, 0.1, 0.8]),
+    );
+    frame.add(Circle::new().position([220.0, 35.0]).radius(20.0_f32));
+    frame.add(
+        Line::new()
+            .start([10.0, 80.0])
+            .end([250.0, 110.0])
+            .thickness(3.0_f32)
+            .color([0.1, 0.9, 0.4, 1.0]),
+    );
+    frame.add_instanced(
+        Rectangle::new()
+            .position([10.0, 120.0])
+            .dimensions([60.0, 30.0])
+            .color([1.0, 1.0, 0.0, 0.6]),
+    );
+    frame.add_instanced(
+        Circle::new()
+            .position([110.0, 135.0])
+            .radius(15.0_f32)
+            .filled(true)
+            .color([1.0, 0.0, 1.0, 1.0]),
+    );
+    check("shapes", &frame, 260, 160);
+}
+
+/// An outlined circle, a filled one with a border and a rectangle with a border.
+fn outlined_shapes() -> (Circle, Circle, Rectangle<'static>) {
+    (
+        Circle::new()
+            .position([30.0, 30.0])
+            .radius(20.0_f32)
+            .thickness(3.0_f32),
+        Circle::new()
+            .position([85.0, 30.0])
+            .radius(20.0_f32)
+            .filled(true)
+            .color([0.2, 0.4, 0.9, 1.0])
+            .border(Outline::new().thickness(2.0).color([1.0, 0.8, 0.2, 1.0])),
+        Rectangle::new()
+            .position([125.0, 12.0])
+            .dimensions([50.0, 36.0])
+            .color([0.9, 0.3, 0.1, 1.0])
+            .border(Outline::new().thickness(2.0)),
+    )
+}
+
+#[test]
+fn outlines() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    // drawn from vertices, then as instances, which have to look the same
+    let mut vertices = overlay.new_frame();
+    vertices.set_anti_aliasing(true);
+    let (ring, disc, rectangle) = outlined_shapes();
+    vertices.add(ring);
+    vertices.add(disc);
+    vertices.add(rectangle);
+
+    let mut instances = overlay.new_frame();
+    let (ring, disc, rectangle) = outlined_shapes();
+    instances.add_instanced(ring);
+    instances.add_instanced(disc);
+    instances.add_instanced(rectangle);
+    assert_eq!(instances.stats().instances, 3);
+
+    let mut renderer = SoftwareRenderer::new();
+    let [vertices, instances] =
+        [vertices, instances].map(|frame| renderer.render_image(&frame, 190, 60));
+    // circles are polygons on the CPU, their edges differ a little
+    let comparison = golden::compare(&vertices, &instances, 32);
+    assert_eq!(comparison.mismatched, 0, "{:?}", comparison);
+    if let Err(e) = golden().check("outlines", &vertices) {
+        panic!("outlines: {}", e);
+    }
+}
+
+#[test]
+fn anti_aliasing() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    frame.set_anti_aliasing(true);
+    frame.add(
+        Rectangle::new()
+            .position([10.0, 10.0])
+            .dimensions([60.0, 40.0])
+            .color([0.2, 0.4, 0.9, 1.0]),
+    );
+    // strokes a pixel thin, where the fringe shows the most
+    frame.add(
+        Circle::new()
+            .position([40.0, 30.0])
+            .radius(14.0_f32)
+            .thickness(1.0_f32),
+    );
+    frame.add(
+        Circle::new()
+            .position([110.0, 30.0])
+            .radius(20.0_f32)
+            .filled(true)
+            .color([0.9, 0.3, 0.1, 1.0]),
+    );
+    frame.add(
+        Line::new()
+            .start([10.0, 70.0])
+            .end([150.0, 85.0])
+            .thickness(1.0_f32)
+            .color([0.1, 0.9, 0.4, 1.0]),
+    );
+    frame.push_transform();
+    frame.translate([40.0, 75.0]);
+    frame.rotate(0.3);
+    frame.add(
+        Rectangle::new()
+            .position([-10.0, -10.0])
+            .dimensions([20.0, 20.0])
+            .color([1.0, 1.0, 0.0, 1.0]),
+    );
+    frame.pop_transform();
+    check("anti_aliasing", &frame, 160, 100);
+}
+
+#[test]
+fn layers() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    // added from the top down, each one overlapping the ones below it
+    frame.push_layer(Layer::TOOLTIP);
+    frame.add(
+        Rectangle::new()
+            .position([70.0, 50.0])
+            .dimensions([50.0, 40.0])
+            .color([1.0, 0.0, 0.0, 1.0]),
+    );
+    frame.push_layer(Layer::HUD);
+    frame.add_instanced(
+        Circle::new()
+            .position([70.0, 50.0])
+            .radius(25.0_f32)
+            .filled(true)
+            .color([0.0, 1.0, 0.0, 1.0]),
+    );
+    frame.pop_layer();
+    frame.pop_layer();
+    frame.add(
+        Rectangle::new()
+            .position([30.0, 20.0])
+            .dimensions([50.0, 40.0])
+            .color([0.0, 0.0, 1.0, 1.0]),
+    );
+    frame.add_to_layer(
+        Layer::BACKGROUND,
+        Rectangle::new()
+            .dimensions([160.0, 100.0])
+            .color([0.3, 0.3, 0.3, 1.0]),
+    );
+    // on the default layer again, so over the blue rectangle added before it
+    frame.add(
+        Rectangle::new()
+            .position([10.0, 10.0])
+            .dimensions([30.0, 30.0])
+            .color([1.0, 1.0, 0.0, 1.0]),
+    );
+    check("layers", &frame, 160, 100);
+}
+
+#[test]
+fn draw_list() {
+    let mut overlay = Overlay::new().expect("failed to create the overlay");
+    let mut list = DrawList::new();
+    let mut recording = overlay.new_frame();
+    recording.add(
+        Rectangle::new()
+            .dimensions([70.0, 30.0])
+            .color([0.2, 0.4, 0.9, 1.0]),
+    );
+    recording.push_clip_rect([[0.0, 0.0], [50.0, 30.0]]);
+    recording.add(
+        Circle::new()
+            .position([50.0, 15.0])
+            .radius(12.0_f32)
+            .filled(true)
+            .color([0.9, 0.3, 0.1, 1.0]),
+    );
+    recording.pop_clip_rect();
+    recording.add(Text::new("HUD").position([4.0, 4.0]).size(16.0));
+    list.record(recording);
+    assert!(list.is_valid());
+
+    // the list keeps its glyphs, the overlay can change while it's kept
+    overlay
+        .add_font_from_memory(
+            include_bytes!("../assets/fonts/NotoSansMono-Regular.ttf"),
+            10.0,
+            1,
+        )
+        .expect("failed to load the font");
+    overlay.push_font(1);
+
+    let mut frame = overlay.new_frame();
+    frame.add_draw_list(&list, [0.0, 0.0]);
+    frame.add_draw_list(&list, [80.0, 10.5]);
+    // the recorded clip rectangle moves along, and is intersected with the current one
+    frame.push_clip_rect([[10.0, 45.0], [160.0, 60.0]]);
+    frame.add_draw_list(&list, [20.0, 40.0]);
+    frame.pop_clip_rect();
+    frame.add(Text::new("live").position([100.0, 50.0]));
+    check("draw_list", &frame, 160, 80);
+}
+
+#[test]
+fn clipping_transforms_and_blending() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .dimensions([200.0, 120.0])
+            .color([0.3, 0.3, 0.3, 1.0]),
+    );
+
+    frame.push_clip_rect([[10.0, 10.0], [60.0, 40.0]]);
+    frame.add(
+        Circle::new()
+            .position([35.0, 40.0])
+            .radius(30.0_f32)
+            .filled(true),
+    );
+    frame.pop_clip_rect();
+
+    frame.push_transform();
+    frame.translate([120.0, 40.0]);
+    frame.rotate(std::f32::consts::FRAC_PI_4);
+    frame.add(
+        Rectangle::new()
+            .position([-15.0, -15.0])
+            .dimensions([30.0, 30.0])
+            .color([0.2, 0.8, 0.2, 1.0]),
+    );
+    frame.pop_transform();
+
+    frame.push_alpha(0.5);
+    frame.add_with_blend_mode(
+        BlendMode::Additive,
+        Rectangle::new()
+            .position([10.0, 70.0])
+            .dimensions([50.0, 40.0])
+            .color([0.8, 0.2, 0.0, 1.0]),
+    );
+    frame.pop_alpha();
+    frame.add_with_blend_mode(
+        BlendMode::Multiply,
+        Rectangle::new()
+            .position([80.0, 70.0])
+            .dimensions([50.0, 40.0])
+            .color([0.5, 0.9, 0.2, 1.0]),
+    );
+    check("clipping_transforms_and_blending", &frame, 200, 120);
+}
+
+#[test]
+fn render_to_texture() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut renderer = SoftwareRenderer::new();
+    let minimap = Texture2D::render_target((60, 40));
+
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .dimensions([60.0, 40.0])
+            .color([0.1, 0.1, 0.4, 0.8]),
+    );
+    frame.add(
+        Rectangle::new()
+            .dimensions([10.0, 5.0])
+            .color([1.0, 0.0, 0.0, 1.0]),
+    );
+    frame.push_clip_rect([[10.0, 20.0], [40.0, 35.0]]);
+    frame.add(
+        Circle::new()
+            .position([25.0, 35.0])
+            .radius(15.0_f32)
+            .filled(true)
+            .color([0.0, 1.0, 0.0, 1.0]),
+    );
+    frame.pop_clip_rect();
+    renderer
+        .render_to_texture(&minimap, &frame)
+        .expect("failed to draw into the render target");
+    assert!(matches!(
+        renderer.render_to_texture(&Texture2D::new(vec![0; 4], (1, 1)), &frame),
+        Err(OverlayError::NotRenderTarget)
+    ));
+
+    // drawn twice, once scaled up, the top left corner stays at the top left
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .position([5.0, 5.0])
+            .dimensions([60.0, 40.0])
+            .texture(&minimap),
+    );
+    frame.add(
+        Rectangle::new()
+            .position([75.0, 5.0])
+            .dimensions([120.0, 80.0])
+            .texture(&minimap),
+    );
+    let image = renderer.render_image(&frame, 200, 90);
+    if let Err(e) = golden().check("render_to_texture", &image) {
+        panic!("render_to_texture: {}", e);
+    }
+}
+
+#[test]
+fn stats_panel() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let mut frame = overlay.new_frame();
+    frame.add(
+        Rectangle::new()
+            .dimensions([260.0, 130.0])
+            .color([0.2, 0.5, 0.8, 1.0]),
+    );
+    frame.add_instanced(Rectangle::new().dimensions([10.0, 10.0]));
+    assert_eq!(frame.stats().primitives, 2);
+    assert_eq!(frame.stats().instances, 1);
+
+    // timings vary between runs, so the panel shows fixed statistics
+    let stats = FrameStats {
+        primitives: 1234,
+        instances: 56,
+        vertices: 7890,
+        draw_calls: 12,
+        texture_switches: 3,
+        upload_bytes: 4096,
+        build_time: Duration::from_micros(1250),
+        submit_time: Duration::from_micros(420),
+    };
+    frame.add(StatsPanel::new(stats).position([10.0, 10.0]));
+    check("stats_panel", &frame, 260, 130);
+}
@@ -0,0 +1,122 @@
+//! Primitives defined outside of the crate, the way applications add their own.
+
+use overlaylib::{
+    font::Font,
+    frame::{DrawCommand, Frame},
+    primitives::{Geometry, Primitive, Rectangle, Resolver, Text},
+    software::SoftwareRenderer,
+    texture::Texture2D,
+    Overlay,
+};
+
+/// A textured square.
+struct Icon<'a> {
+    texture: &'a Texture2D,
+    position: [f32; 2],
+}
+
+impl<'a> Primitive<'a> for Icon<'a> {
+    fn get_geometry(&self) -> Geometry {
+        Rectangle::new()
+            .position(self.position)
+            .dimensions([16.0, 16.0])
+            .get_geometry()
+    }
+
+    fn get_texture(&self) -> Option<&'a Texture2D> {
+        Some(self.texture)
+    }
+}
+
+/// Text drawn with the font added with `font_id`, or the current one if there's none.
+struct Label<'a> {
+    text: String,
+    position: [f32; 2],
+    font_id: usize,
+    font: Option<&'a Font>,
+}
+
+impl<'a> Label<'a> {
+    fn new(text: &str, position: [f32; 2], font_id: usize) -> Self {
+        Self {
+            text: text.to_string(),
+            position,
+            font_id,
+            font: None,
+        }
+    }
+}
+
+impl<'a> Primitive<'a> for Label<'a> {
+    fn resolve(&mut self, resolver: &Resolver<'a>) {
+        self.font = resolver
+            .font(self.font_id)
+            .or_else(|| resolver.current_font());
+    }
+
+    fn get_geometry(&self) -> Geometry {
+        match self.font {
+            Some(font) => Text::new(self.text.as_str())
+                .position(self.position)
+                .font(font)
+                .get_geometry(),
+            None => Geometry::new(),
+        }
+    }
+
+    fn get_font(&self) -> Option<&'a Font> {
+        self.font
+    }
+}
+
+/// Returns the id of the texture of every immediate draw command.
+fn texture_ids(frame: &Frame<'_>) -> Vec<Option<u64>> {
+    frame
+        .commands()
+        .filter_map(|command| match command {
+            DrawCommand::Immediate(buffer) => Some(buffer.texture.map(Texture2D::id)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn texture_hook() {
+    let overlay = Overlay::new().expect("failed to create the overlay");
+    let texture = Texture2D::new([255, 0, 0, 255].repeat(4), (2, 2));
+
+    let mut frame = overlay.new_frame();
+    frame.add(Icon {
+        texture: &texture,
+        position: [2.0, 2.0],
+    });
+    assert_eq!(frame.stats().primitives, 1);
+    assert_eq!(frame.stats().vertices, 4);
+    assert_eq!(texture_ids(&frame), [Some(texture.id())]);
+
+    let image = SoftwareRenderer::new().render_image(&frame, 20, 20);
+    assert_eq!(image.get_pixel(10, 10).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(1, 1).0, [0, 0, 0, 0]);
+}
+
+#[test]
+fn resolve_and_font_hooks() {
+    let mut overlay = Overlay::new().expect("failed to create the overlay");
+    overlay
+        .add_font_from_memory(
+            include_bytes!("../assets/fonts/NotoSansMono-Regular.ttf"),
+            12.0,
+            1,
+        )
+        .expect("failed to load the font");
+
+    let mut frame = overlay.new_frame();
+    frame.add(Label::new("small", [0.0, 0.0], 1));
+    // no font 2, so it falls back to the default font on top of the stack
+    frame.add(Label::new("default", [0.0, 20.0], 2));
+
+    let small = overlay.fonts[&1].get_texture().id();
+    let default = overlay.current_font().unwrap().get_texture().id();
+    assert_ne!(small, default);
+    assert_eq!(texture_ids(&frame), [Some(small), Some(default)]);
+}